@@ -0,0 +1,267 @@
+use crate::os::{self, Path, PathBuf, ReadDirError, ReadError, WriteError};
+use crate::renderer::Serialize;
+use crate::yaml_parser::{self, Deserialize, ParseError, YamlParser};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+const BACKUP_DIR: &str = "backups";
+const MANIFEST_FILE: &str = "manifest.yaml";
+
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error("no backups to roll back to")]
+    NoBackups,
+
+    #[error("could not list backups -> {0}")]
+    ReadDir(#[from] ReadDirError),
+
+    #[error("read failed -> {0}")]
+    Read(#[from] ReadError),
+
+    #[error("parse failed -> {0}")]
+    Parse(#[from] ParseError),
+
+    #[error("write failed -> {0}")]
+    Write(#[from] WriteError),
+}
+
+#[derive(Deserialize, Serialize)]
+struct Entry {
+    target: PathBuf,
+    /// The file holding the previous contents, or `None` if the target did not exist.
+    backup: Option<String>,
+    /// The previous contents, which are only written once the generation is saved.
+    #[serde(skip)]
+    contents: Option<Vec<u8>>,
+}
+
+/// The contents of the targets before one run of rswal, stored in a directory of the cache.
+pub struct Generation {
+    dir: PathBuf,
+    entries: Vec<Entry>,
+}
+
+impl Generation {
+    pub fn new(cache_dir: &Path) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or_default();
+
+        Generation {
+            dir: cache_dir.join(BACKUP_DIR).join(timestamp.to_string()),
+            entries: vec![],
+        }
+    }
+
+    /// The stored generations, oldest first.
+    fn list(cache_dir: &Path) -> Result<Vec<PathBuf>, BackupError> {
+        let backup_dir = cache_dir.join(BACKUP_DIR);
+        if !backup_dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut generations = os::read_dir(backup_dir)?
+            .into_iter()
+            .filter_map(|dir| {
+                let timestamp = dir.file_name()?.to_str()?.parse::<u128>().ok()?;
+                Some((timestamp, dir))
+            })
+            .collect::<Vec<(u128, PathBuf)>>();
+        generations.sort();
+
+        Ok(generations.into_iter().map(|(_, dir)| dir).collect())
+    }
+
+    /// The most recent generation.
+    pub fn latest(cache_dir: &Path) -> Result<Self, BackupError> {
+        let dir = Self::list(cache_dir)?.pop().ok_or(BackupError::NoBackups)?;
+        let manifest = os::read_file(dir.join(MANIFEST_FILE))?;
+
+        Ok(Generation {
+            entries: YamlParser::parse(&manifest)?,
+            dir,
+        })
+    }
+
+    /// Removes all but the `keep` most recent generations.
+    pub fn prune(cache_dir: &Path, keep: usize) -> Result<(), BackupError> {
+        let generations = Self::list(cache_dir)?;
+        let excess = generations.len().saturating_sub(keep);
+
+        for dir in &generations[..excess] {
+            os::remove_dir(dir)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remembers the current contents of `target`.
+    pub fn back_up(&mut self, target: &Path) -> Result<(), BackupError> {
+        let contents = match os::read_bytes(target) {
+            Ok(contents) => Some(contents),
+            Err(ReadError::FileNotFound) => None,
+            Err(err) => return Err(err.into()),
+        };
+        self.entries.push(Entry {
+            target: target.to_path_buf(),
+            backup: contents.as_ref().map(|_| self.entries.len().to_string()),
+            contents,
+        });
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Whether the most recent generation already holds these contents of every target, in which
+    /// case rolling back to this generation would not restore anything the latest one does not.
+    pub fn is_in_latest(&self, cache_dir: &Path) -> Result<bool, BackupError> {
+        let latest = match Self::latest(cache_dir) {
+            Ok(latest) => latest,
+            Err(BackupError::NoBackups) => return Ok(false),
+            Err(err) => return Err(err),
+        };
+
+        for entry in &self.entries {
+            let Some(previous) = latest
+                .entries
+                .iter()
+                .find(|previous| previous.target == entry.target)
+            else {
+                return Ok(false);
+            };
+            let contents = match &previous.backup {
+                Some(backup) => Some(os::read_bytes(latest.dir.join(backup))?),
+                None => None,
+            };
+            if contents != entry.contents {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    pub fn save(&self) -> Result<(), BackupError> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+
+        os::create_dir(&self.dir)?;
+        for entry in &self.entries {
+            if let (Some(backup), Some(contents)) = (&entry.backup, &entry.contents) {
+                os::write_to_file(self.dir.join(backup), contents)?;
+            }
+        }
+        os::write_to_file(
+            self.dir.join(MANIFEST_FILE),
+            &yaml_parser::to_string(&self.entries)?,
+        )?;
+
+        Ok(())
+    }
+
+    /// Puts every target back the way it was and removes the generation, returning the targets.
+    pub fn restore(self) -> Result<Vec<PathBuf>, BackupError> {
+        for entry in &self.entries {
+            match &entry.backup {
+                Some(backup) => {
+                    let contents = os::read_bytes(self.dir.join(backup))?;
+                    os::write_to_file(&entry.target, &contents)?;
+                }
+                None if entry.target.exists() => os::remove_file(&entry.target)?,
+                None => {}
+            }
+        }
+        os::remove_dir(&self.dir)?;
+
+        Ok(self.entries.into_iter().map(|entry| entry.target).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn back_up_and_restore() {
+        let dir = tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let (existing, created) = (dir.path().join("existing"), dir.path().join("created"));
+        os::write_to_file(&existing, "before").unwrap();
+        assert!(matches!(
+            Generation::latest(&cache_dir),
+            Err(BackupError::NoBackups)
+        ));
+
+        let mut generation = Generation::new(&cache_dir);
+        generation.back_up(&existing).unwrap();
+        generation.back_up(&created).unwrap();
+        generation.save().unwrap();
+        os::write_to_file(&existing, "after").unwrap();
+        os::write_to_file(&created, "after").unwrap();
+
+        let restored = Generation::latest(&cache_dir).unwrap().restore().unwrap();
+        assert_eq!(restored, [existing.clone(), created.clone()]);
+        assert_eq!(os::read_file(&existing).unwrap(), "before");
+        assert!(!created.exists());
+        assert!(matches!(
+            Generation::latest(&cache_dir),
+            Err(BackupError::NoBackups)
+        ));
+    }
+
+    #[test]
+    fn is_in_latest() {
+        let dir = tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let (first, second) = (dir.path().join("first"), dir.path().join("second"));
+        os::write_to_file(&first, "first").unwrap();
+
+        let generation = |targets: &[&PathBuf]| {
+            let mut generation = Generation::new(&cache_dir);
+            targets
+                .iter()
+                .for_each(|target| generation.back_up(target).unwrap());
+            generation
+        };
+        assert!(!generation(&[&first]).is_in_latest(&cache_dir).unwrap());
+        generation(&[&first, &second]).save().unwrap();
+
+        assert!(generation(&[&first]).is_in_latest(&cache_dir).unwrap());
+        assert!(generation(&[&second, &first])
+            .is_in_latest(&cache_dir)
+            .unwrap());
+        os::write_to_file(&second, "second").unwrap();
+        assert!(!generation(&[&second]).is_in_latest(&cache_dir).unwrap());
+        os::write_to_file(&first, "changed").unwrap();
+        assert!(!generation(&[&first]).is_in_latest(&cache_dir).unwrap());
+    }
+
+    #[test]
+    fn prune() {
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("target");
+        os::write_to_file(&target, "").unwrap();
+
+        for timestamp in [3, 1, 20] {
+            let mut generation = Generation {
+                dir: dir.path().join(BACKUP_DIR).join(timestamp.to_string()),
+                entries: vec![],
+            };
+            generation.back_up(&target).unwrap();
+            generation.save().unwrap();
+        }
+
+        Generation::prune(dir.path(), 2).unwrap();
+        let generations = Generation::list(dir.path()).unwrap();
+        assert_eq!(
+            generations,
+            [3, 20].map(|timestamp| dir.path().join(BACKUP_DIR).join(timestamp.to_string()))
+        );
+    }
+}
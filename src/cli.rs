@@ -1,7 +1,11 @@
+use crate::export::ExportFormat;
+use crate::import::Format;
 use crate::os;
 use crate::renderer::Value;
+use crate::theme::Variant;
 use crate::yaml_parser::YamlParser;
-use clap::Parser as ArgParser;
+use clap::error::{Error as ArgError, ErrorKind as ArgErrorKind};
+use clap::{Args as ArgGroup, CommandFactory, Parser as ArgParser, Subcommand};
 use std::env;
 use std::path::{Path, PathBuf};
 
@@ -11,17 +15,92 @@ const BINARY_NAME: &str = env!("CARGO_PKG_NAME");
 #[derive(ArgParser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
+    /// Set the configuration directory.
+    #[arg(long, global = true, default_value=default_config_dir().into_os_string(), value_parser = parse_path)]
+    pub config_dir: PathBuf,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// List available themes.
+    #[arg(long, hide = true)]
+    list_themes: bool,
+
+    // Running without a subcommand is an alias for `apply`.
+    #[command(flatten)]
+    apply: ApplyArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Apply a theme.
+    Apply(ApplyArgs),
+
+    /// List available themes.
+    List,
+
+    /// Preview the colors of a theme in the terminal.
+    Preview(ThemeArgs),
+
+    /// Check the configuration, templates, hooks and theme for errors.
+    Validate(ThemeArgs),
+
+    /// Check that the colors of a theme are readable on its background.
+    Lint(LintArgs),
+
+    /// Show the last applied theme.
+    Current,
+
+    /// Export a theme to another color scheme format.
+    Export(ExportArgs),
+
+    /// Convert a color scheme of another application into a theme.
+    Import(ImportArgs),
+
+    /// Restore the targets overwritten by the last run.
+    Rollback,
+}
+
+#[derive(ArgGroup, Clone, Debug)]
+pub struct ThemeArgs {
     /// Set the theme.
     #[arg(short, long)]
     pub theme: Option<String>,
 
-    /// List available themes.
+    /// Generate the theme from a wallpaper image.
+    #[arg(short, long, conflicts_with = "theme", value_parser = parse_path)]
+    pub image: Option<PathBuf>,
+
+    /// Derive the light or dark variant of the theme.
+    #[arg(long, value_enum)]
+    pub variant: Option<Variant>,
+}
+
+#[derive(ArgGroup, Debug)]
+pub struct ApplyArgs {
+    #[command(flatten)]
+    pub theme: ThemeArgs,
+
+    /// Reapply the last applied theme and variables.
+    #[arg(short, long, conflicts_with_all = ["theme", "image", "variant"])]
+    pub restore: bool,
+
+    /// Apply the theme scheduled for the current time of day.
+    #[arg(long, conflicts_with_all = ["theme", "image", "restore"])]
+    pub auto: bool,
+
+    /// Keep running and render the templates again whenever they, the configuration or the theme
+    /// change.
     #[arg(long)]
-    pub list_themes: bool,
+    pub watch: bool,
 
-    /// Set the configuration directory.
-    #[arg(long, default_value=default_config_dir().into_os_string(), value_parser = parse_config_dir)]
-    pub config_dir: PathBuf,
+    /// Show how the targets would change and which hooks would run, without changing anything.
+    #[arg(long, conflicts_with = "watch")]
+    pub dry_run: bool,
+
+    /// Send the colors to every open terminal as escape sequences.
+    #[arg(short, long)]
+    pub sequences: bool,
 
     /// Specify hooks.
     #[arg(short = 'H', long, num_args = 1..)]
@@ -32,9 +111,94 @@ pub struct Args {
     pub variables: Option<Vec<(String, Value)>>,
 }
 
+impl ApplyArgs {
+    fn is_empty(&self) -> bool {
+        self.theme.theme.is_none()
+            && self.theme.image.is_none()
+            && self.theme.variant.is_none()
+            && !self.restore
+            && !self.auto
+            && !self.watch
+            && !self.dry_run
+            && !self.sequences
+            && self.hooks.is_none()
+            && self.variables.is_none()
+    }
+}
+
+#[derive(ArgGroup, Debug)]
+pub struct ExportArgs {
+    /// The format to export to.
+    #[arg(value_enum, default_value_t = ExportFormat::Yaml)]
+    pub format: ExportFormat,
+
+    #[command(flatten)]
+    pub theme: ThemeArgs,
+
+    /// Write to a file instead of the standard output.
+    #[arg(short, long, value_parser = parse_path)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(ArgGroup, Debug)]
+pub struct LintArgs {
+    #[command(flatten)]
+    pub theme: ThemeArgs,
+
+    /// The minimum contrast ratio against the background.
+    #[arg(long, default_value_t = 4.5)]
+    pub min_contrast: f32,
+
+    /// Adjust the lightness of failing colors and print the fixed theme.
+    #[arg(long)]
+    pub fix: bool,
+}
+
+#[derive(ArgGroup, Debug)]
+pub struct ImportArgs {
+    /// The color scheme to import.
+    #[arg(value_parser = parse_path)]
+    pub file: PathBuf,
+
+    /// Set the format instead of guessing it from the file name.
+    #[arg(short, long)]
+    pub format: Option<Format>,
+
+    /// Set the name of the theme instead of deriving it from the file name.
+    #[arg(short, long)]
+    pub name: Option<String>,
+
+    /// Overwrite an existing theme with the same name.
+    #[arg(long)]
+    pub force: bool,
+}
+
 impl Args {
     pub fn parse() -> Self {
         <Args as ArgParser>::parse()
+            .check_conflicts()
+            .unwrap_or_else(|err| err.exit())
+    }
+
+    // `args_conflicts_with_subcommands` would also reject global arguments, so the conflicts
+    // between the `apply` alias and the subcommands are checked by hand.
+    fn check_conflicts(self) -> Result<Self, ArgError> {
+        if self.command.is_some() && (self.list_themes || !self.apply.is_empty()) {
+            return Err(<Args as CommandFactory>::command().error(
+                ArgErrorKind::ArgumentConflict,
+                "apply options cannot be used with a subcommand",
+            ));
+        }
+
+        Ok(self)
+    }
+
+    pub fn into_command(self) -> Command {
+        match self.command {
+            Some(command) => command,
+            None if self.list_themes => Command::List,
+            None => Command::Apply(self.apply),
+        }
     }
 }
 
@@ -43,9 +207,9 @@ fn default_config_dir() -> PathBuf {
     Path::new(&config_dir).join(BINARY_NAME)
 }
 
-fn parse_config_dir(path: &str) -> Result<PathBuf, String> {
+fn parse_path(path: &str) -> Result<PathBuf, String> {
     let path = Path::new(path);
-    let resolved = os::resolve_path(&path);
+    let resolved = os::resolve_path(path);
 
     resolved.ok_or("could not resolve the provided path".to_string())
 }
@@ -72,4 +236,101 @@ mod tests {
 
         Args::command().debug_assert()
     }
+
+    #[test]
+    fn subcommands() {
+        let parse = |args: &[&str]| {
+            Args::try_parse_from([BINARY_NAME].iter().chain(args))
+                .and_then(|args| args.check_conflicts())
+        };
+
+        let command = parse(&["-t", "monokai", "-s"]).unwrap().into_command();
+        assert!(matches!(
+            command,
+            Command::Apply(ApplyArgs { theme: ThemeArgs { theme: Some(ref theme), .. }, sequences: true, .. })
+                if theme == "monokai"
+        ));
+
+        let command = parse(&["apply", "--restore"]).unwrap().into_command();
+        assert!(matches!(
+            command,
+            Command::Apply(ApplyArgs { restore: true, .. })
+        ));
+        assert!(parse(&["apply", "--restore", "--variant", "light"]).is_err());
+
+        let command = parse(&["--auto"]).unwrap().into_command();
+        assert!(matches!(
+            command,
+            Command::Apply(ApplyArgs { auto: true, .. })
+        ));
+        assert!(parse(&["--auto", "-t", "monokai"]).is_err());
+
+        let command = parse(&["--watch", "-t", "monokai"]).unwrap().into_command();
+        assert!(matches!(
+            command,
+            Command::Apply(ApplyArgs { watch: true, .. })
+        ));
+
+        let command = parse(&["preview", "--variant", "light"])
+            .unwrap()
+            .into_command();
+        assert!(matches!(
+            command,
+            Command::Preview(ThemeArgs {
+                variant: Some(Variant::Light),
+                ..
+            })
+        ));
+
+        let command = parse(&["rollback"]).unwrap().into_command();
+        assert!(matches!(command, Command::Rollback));
+
+        let command = parse(&["--list-themes"]).unwrap().into_command();
+        assert!(matches!(command, Command::List));
+
+        let command = parse(&["export", "-t", "monokai", "-o", "/tmp/theme.yaml"])
+            .unwrap()
+            .into_command();
+        assert!(matches!(
+            command,
+            Command::Export(ExportArgs { format: ExportFormat::Yaml, output: Some(ref output), .. })
+                if output == Path::new("/tmp/theme.yaml")
+        ));
+
+        let command = parse(&["lint", "--min-contrast", "3", "--fix"])
+            .unwrap()
+            .into_command();
+        assert!(matches!(
+            command,
+            Command::Lint(LintArgs {
+                min_contrast: 3.0,
+                fix: true,
+                ..
+            })
+        ));
+
+        let command = parse(&["export", "alacritty"]).unwrap().into_command();
+        assert!(matches!(
+            command,
+            Command::Export(ExportArgs {
+                format: ExportFormat::Alacritty,
+                output: None,
+                ..
+            })
+        ));
+
+        let command = parse(&["import", "dracula.json", "-f", "windows-terminal"])
+            .unwrap()
+            .into_command();
+        assert!(matches!(
+            command,
+            Command::Import(ImportArgs {
+                format: Some(Format::WindowsTerminal),
+                ..
+            })
+        ));
+
+        assert!(parse(&["--config-dir", "/tmp", "list"]).is_ok());
+        assert!(parse(&["-t", "monokai", "list"]).is_err());
+    }
 }
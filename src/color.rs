@@ -1,15 +1,33 @@
-use palette::{self, Darken, Hsl, IntoColor, Lighten, Saturate, ShiftHue, Srgb};
-use std::fmt::Debug;
+use crate::renderer::{Serialize, Serializer};
+use crate::yaml_parser::{de, Deserialize, Deserializer};
+use palette::color_difference::Wcag21RelativeContrast;
+use palette::convert::FromColorUnclamped;
+use palette::{
+    self, Clamp, Darken, FromColor, Hsl, IntoColor, Lighten, Oklab, Oklch, Saturate, ShiftHue, Srgb,
+};
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum ColorError {
-    #[error("invalid hex format: should be #RRGGBB")]
+    #[error("invalid hex format: should be #RGB, #RGBA, #RRGGBB or #RRGGBBAA")]
     InvalidHex,
+
+    #[error("invalid arguments to {0}()")]
+    InvalidArguments(String),
+
+    #[error("unknown color function '{0}'")]
+    UnknownFunction(String),
+
+    #[error("unknown color name '{0}'")]
+    UnknownName(String),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Color {
     value: Srgb<f32>,
+    alpha: f32,
 }
 
 impl Color {
@@ -20,25 +38,121 @@ impl Color {
                 green as f32 / 255.0,
                 blue as f32 / 255.0,
             ),
+            alpha: 1.0,
         }
     }
 
+    /// Parses `#RGB`, `#RGBA`, `#RRGGBB` and `#RRGGBBAA`.
     pub fn from_hex(hex: &str) -> Result<Self, ColorError> {
-        if hex.len() != 7 || !hex.starts_with('#') {
+        let digits = hex.strip_prefix('#').ok_or(ColorError::InvalidHex)?;
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
             return Err(ColorError::InvalidHex);
         }
 
-        let red = u8::from_str_radix(&hex[1..3], 16);
-        let green = u8::from_str_radix(&hex[3..5], 16);
-        let blue = u8::from_str_radix(&hex[5..7], 16);
+        let channels = match digits.len() {
+            3 | 4 => digits
+                .chars()
+                .map(|c| u8::from_str_radix(&c.to_string(), 16).map(|value| value * 17))
+                .collect::<Result<Vec<u8>, _>>(),
+            6 | 8 => (0..digits.len())
+                .step_by(2)
+                .map(|index| u8::from_str_radix(&digits[index..index + 2], 16))
+                .collect(),
+            _ => return Err(ColorError::InvalidHex),
+        }
+        .map_err(|_| ColorError::InvalidHex)?;
+
+        let color = Color::new(channels[0], channels[1], channels[2]);
+        Ok(match channels.get(3) {
+            Some(&alpha) => color.with_alpha(alpha as f32 / 255.0),
+            None => color,
+        })
+    }
 
-        match (red, green, blue) {
-            (Ok(red), Ok(green), Ok(blue)) => Ok(Color::new(red, green, blue)),
-            _ => Err(ColorError::InvalidHex),
+    fn from_function(function: &str, arguments: &str) -> Result<Self, ColorError> {
+        let invalid = || ColorError::InvalidArguments(function.to_string());
+
+        // Both the legacy `rgba(r, g, b, a)` and the modern `rgb(r g b / a)` forms are accepted.
+        let (channels, alpha) = match arguments.split_once('/') {
+            Some((channels, alpha)) => (channels, Some(alpha)),
+            None => (arguments, None),
+        };
+        let mut channels = channels
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|channel| !channel.is_empty())
+            .collect::<Vec<&str>>();
+        let alpha = match (alpha, channels.len()) {
+            (Some(alpha), 3) => Some(alpha.trim()),
+            (None, 4) => channels.pop(),
+            (None, 3) => None,
+            _ => return Err(invalid()),
+        };
+        let alpha = match alpha {
+            Some(alpha) => Component::parse(alpha).ok_or_else(invalid)?.fraction(1.0),
+            None => 1.0,
+        };
+        let [first, second, third] = [0, 1, 2].map(|index| Component::parse(channels[index]));
+        let (Some(first), Some(second), Some(third)) = (first, second, third) else {
+            return Err(invalid());
+        };
+
+        let value = match function.to_ascii_lowercase().as_str() {
+            "rgb" | "rgba" => Srgb::new(
+                first.fraction(255.0),
+                second.fraction(255.0),
+                third.fraction(255.0),
+            ),
+            "hsl" | "hsla" => Hsl::new(
+                first.degrees(),
+                second.fraction(100.0),
+                third.fraction(100.0),
+            )
+            .into_color(),
+            "oklch" => to_gamut(Oklch::new(
+                first.fraction(1.0),
+                // A chroma of 100% corresponds to 0.4.
+                match second {
+                    Component::Number(chroma) => chroma,
+                    Component::Percentage(chroma) => chroma * 0.004,
+                },
+                third.degrees(),
+            )),
+            _ => return Err(ColorError::UnknownFunction(function.to_string())),
+        };
+
+        Ok(Color {
+            value: value.clamp(),
+            alpha: alpha.clamp(0.0, 1.0),
+        })
+    }
+
+    fn from_name(name: &str) -> Result<Self, ColorError> {
+        let name = name.to_ascii_lowercase();
+        if name == "transparent" {
+            return Ok(Color::new(0, 0, 0).with_alpha(0.0));
+        }
+
+        palette::named::from_str(&name)
+            .map(|color| Color::new(color.red, color.green, color.blue))
+            .ok_or(ColorError::UnknownName(name))
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    pub fn with_alpha(self, alpha: f32) -> Self {
+        Color {
+            alpha: alpha.clamp(0.0, 1.0),
+            ..self
         }
     }
 
-    pub fn to_hex(&self) -> String {
+    fn alpha_byte(self) -> u8 {
+        (self.alpha * 255.0).round() as u8
+    }
+
+    pub fn to_hex(self) -> String {
         let color: Srgb<u8> = self.value.into_format();
 
         format!("#{:02x}{:02x}{:02x}", color.red, color.green, color.blue)
@@ -47,12 +161,14 @@ impl Color {
     pub fn lighten(self, factor: f32) -> Self {
         Color {
             value: self.value.lighten(factor),
+            ..self
         }
     }
 
     pub fn darken(self, factor: f32) -> Self {
         Color {
             value: self.value.darken(factor),
+            ..self
         }
     }
 
@@ -61,6 +177,7 @@ impl Color {
 
         Color {
             value: hsl.shift_hue(amount).into_color(),
+            ..self
         }
     }
 
@@ -69,19 +186,317 @@ impl Color {
 
         Color {
             value: hsl.saturate(factor).into_color(),
+            ..self
+        }
+    }
+
+    fn map_oklch(self, map: impl FnOnce(Oklch) -> Oklch) -> Self {
+        let oklch = map(Oklch::from_color(self.value));
+
+        Color {
+            value: to_gamut(oklch),
+            ..self
+        }
+    }
+
+    /// Like [`Color::lighten`], but in OkLCh so that every hue gains the same perceived lightness.
+    pub fn ok_lighten(self, factor: f32) -> Self {
+        self.map_oklch(|oklch| Oklch {
+            l: oklch.l + (1.0 - oklch.l) * factor,
+            ..oklch
+        })
+    }
+
+    pub fn ok_darken(self, factor: f32) -> Self {
+        self.map_oklch(|oklch| Oklch {
+            l: oklch.l * (1.0 - factor),
+            ..oklch
+        })
+    }
+
+    /// Scales the chroma by `1 + factor`, so negative factors desaturate.
+    pub fn ok_saturate(self, factor: f32) -> Self {
+        self.map_oklch(|oklch| Oklch {
+            chroma: (oklch.chroma * (1.0 + factor)).max(0.0),
+            ..oklch
+        })
+    }
+
+    pub fn ok_shift_hue(self, amount: f32) -> Self {
+        self.map_oklch(|oklch| oklch.shift_hue(amount))
+    }
+
+    /// Mirrors the perceived lightness while keeping hue and chroma, turning dark colors light and
+    /// light colors dark.
+    pub fn invert_lightness(self) -> Self {
+        self.map_oklch(|oklch| Oklch {
+            l: 1.0 - oklch.l,
+            ..oklch
+        })
+    }
+
+    /// Whether the color contrasts more with white than with black.
+    pub fn is_dark(&self) -> bool {
+        let (black, white) = (Color::new(0, 0, 0), Color::new(255, 255, 255));
+
+        self.contrast(white) > self.contrast(black)
+    }
+
+    /// Interpolates towards `other` in Oklab, where `factor` 0 is `self` and 1 is `other`.
+    pub fn mix(self, other: Color, factor: f32) -> Self {
+        let (from, to) = (
+            Oklab::from_color(self.value),
+            Oklab::from_color(other.value),
+        );
+        let lerp = |from: f32, to: f32| from + (to - from) * factor;
+
+        Color {
+            value: Srgb::from_color(Oklab::new(
+                lerp(from.l, to.l),
+                lerp(from.a, to.a),
+                lerp(from.b, to.b),
+            ))
+            .clamp(),
+            alpha: lerp(self.alpha, other.alpha),
+        }
+    }
+
+    /// `steps` colors evenly spaced from `self` to `other`, both included.
+    pub fn gradient(self, other: Color, steps: usize) -> Vec<Color> {
+        match steps {
+            0 => vec![],
+            1 => vec![self],
+            _ => (0..steps)
+                .map(|step| self.mix(other, step as f32 / (steps - 1) as f32))
+                .collect(),
         }
     }
 
-    pub fn to_rgba(&self, alpha: f32) -> String {
+    /// `count` shades of the color from dark to light, evenly spaced in perceived lightness and
+    /// leaving out black and white.
+    pub fn shades(self, count: usize) -> Vec<Color> {
+        (1..=count)
+            .map(|shade| {
+                self.map_oklch(|oklch| Oklch {
+                    l: shade as f32 / (count + 1) as f32,
+                    ..oklch
+                })
+            })
+            .collect()
+    }
+
+    /// Composites the color over `background` using its alpha.
+    pub fn blend(self, background: Color) -> Self {
+        let alpha = self.alpha + background.alpha * (1.0 - self.alpha);
+        if alpha == 0.0 {
+            return background;
+        }
+
+        let channel = |foreground: f32, background_channel: f32| {
+            (foreground * self.alpha + background_channel * background.alpha * (1.0 - self.alpha))
+                / alpha
+        };
+        let (foreground, back) = (self.value, background.value);
+
+        Color {
+            value: Srgb::new(
+                channel(foreground.red, back.red),
+                channel(foreground.green, back.green),
+                channel(foreground.blue, back.blue),
+            ),
+            alpha,
+        }
+    }
+
+    pub fn to_rgb(self) -> [u8; 3] {
+        let color: Srgb<u8> = self.value.into_format();
+
+        [color.red, color.green, color.blue]
+    }
+
+    pub fn hue(&self) -> f32 {
+        let hsl: Hsl = self.value.into_color();
+
+        hsl.hue.into_positive_degrees()
+    }
+
+    pub fn saturation(&self) -> f32 {
+        let hsl: Hsl = self.value.into_color();
+
+        hsl.saturation
+    }
+
+    pub fn lightness(&self) -> f32 {
+        let hsl: Hsl = self.value.into_color();
+
+        hsl.lightness
+    }
+
+    /// The WCAG 2.1 contrast ratio between two colors, from 1 to 21.
+    pub fn contrast(&self, other: Color) -> f32 {
+        self.value.relative_contrast(other.value)
+    }
+
+    /// Moves the lightness by as little as possible until the color reaches the contrast `ratio`
+    /// against `background`. Ratios which cannot be reached give the most contrasting lightness.
+    pub fn readable(self, background: Color, ratio: f32) -> Self {
+        let hsl: Hsl = self.value.into_color();
+        let with_lightness = |lightness: f32| Color {
+            value: Hsl::new(hsl.hue, hsl.saturation, lightness).into_color(),
+            ..self
+        };
+
+        (0..=100)
+            .flat_map(|step| {
+                let delta = step as f32 / 100.0;
+                [hsl.lightness + delta, hsl.lightness - delta]
+            })
+            .filter(|lightness| (0.0..=1.0).contains(lightness))
+            .map(with_lightness)
+            .find(|color| color.contrast(background) >= ratio)
+            .unwrap_or_else(|| {
+                let (black, white) = (with_lightness(0.0), with_lightness(1.0));
+                match black.contrast(background) > white.contrast(background) {
+                    true => black,
+                    false => white,
+                }
+            })
+    }
+
+    /// Formats the color as `#RRGGBBAA`.
+    pub fn to_hex_rgba(self) -> String {
+        format!("{}{:02x}", self.to_hex(), self.alpha_byte())
+    }
+
+    /// Formats the color as `#AARRGGBB`, the order used by Android and Qt.
+    pub fn to_hex_argb(self) -> String {
+        format!("#{:02x}{}", self.alpha_byte(), &self.to_hex()[1..])
+    }
+
+    /// Formats the color as a `0xAARRGGBB` integer literal, as Hyprland expects.
+    pub fn to_int_argb(self) -> String {
+        format!("0x{:02x}{}", self.alpha_byte(), &self.to_hex()[1..])
+    }
+
+    pub fn to_rgba(self) -> String {
         let color: Srgb<u8> = self.value.into_format();
 
         format!(
             "rgba({}, {}, {}, {:.2})",
-            color.red, color.green, color.blue, alpha
+            color.red, color.green, color.blue, self.alpha
         )
     }
 }
 
+impl Display for Color {
+    /// Opaque colors keep the short form so that existing themes and templates are unchanged.
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self.alpha < 1.0 {
+            true => write!(f, "{}", self.to_hex_rgba()),
+            false => write!(f, "{}", self.to_hex()),
+        }
+    }
+}
+
+impl FromStr for Color {
+    type Err = ColorError;
+
+    /// Parses hex colors, `rgb()`, `rgba()`, `hsl()`, `hsla()`, `oklch()` and CSS color names.
+    fn from_str(color: &str) -> Result<Self, Self::Err> {
+        let color = color.trim();
+        if color.starts_with('#') {
+            return Color::from_hex(color);
+        }
+
+        match color
+            .strip_suffix(')')
+            .and_then(|color| color.split_once('('))
+        {
+            Some((function, arguments)) => Color::from_function(function.trim(), arguments),
+            None => Color::from_name(color),
+        }
+    }
+}
+
+/// Converts to sRGB, reducing the chroma of colors outside the gamut instead of clipping them so
+/// that the lightness and hue are kept.
+fn to_gamut(oklch: Oklch) -> Srgb<f32> {
+    let with_chroma = |chroma: f32| Srgb::<f32>::from_color_unclamped(Oklch { chroma, ..oklch });
+    // Colors at the edge of the gamut drift slightly outside of it in the round trip.
+    let in_gamut = |color: Srgb<f32>| {
+        [color.red, color.green, color.blue]
+            .iter()
+            .all(|channel| (-1e-4..=1.0 + 1e-4).contains(channel))
+    };
+
+    let (mut low, mut high) = (0.0, oklch.chroma);
+    if in_gamut(with_chroma(high)) {
+        low = high;
+    }
+    while high - low > 1e-5 {
+        let chroma = (low + high) / 2.0;
+        match in_gamut(with_chroma(chroma)) {
+            true => low = chroma,
+            false => high = chroma,
+        }
+    }
+
+    with_chroma(low).clamp()
+}
+
+/// A single argument of a color function, either a plain number or a percentage.
+enum Component {
+    Number(f32),
+    Percentage(f32),
+}
+
+impl Component {
+    fn parse(component: &str) -> Option<Self> {
+        let component = component.trim();
+        if let Some(percentage) = component.strip_suffix('%') {
+            return percentage.parse().ok().map(Component::Percentage);
+        }
+
+        component
+            .strip_suffix("deg")
+            .unwrap_or(component)
+            .parse()
+            .ok()
+            .map(Component::Number)
+    }
+
+    /// Scales the component to `0..=1`, where `max` is what a plain number is relative to.
+    fn fraction(&self, max: f32) -> f32 {
+        match self {
+            Component::Number(value) => value / max,
+            Component::Percentage(value) => value / 100.0,
+        }
+    }
+
+    fn degrees(&self) -> f32 {
+        match self {
+            Component::Number(value) => *value,
+            Component::Percentage(value) => value * 3.6,
+        }
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let color = String::deserialize(deserializer)?;
+
+        color
+            .parse()
+            .map_err(|err| de::Error::custom(format!("invalid color '{color}': {err}")))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -92,6 +507,145 @@ mod tests {
 
         let color = Color::from_hex("#ffffff").unwrap();
         assert_eq!(color.to_hex(), "#ffffff");
+
+        assert!(Color::from_hex("#fffff").is_err());
+        assert!(Color::from_hex("#a\u{e9}bcd").is_err());
+    }
+
+    #[test]
+    fn syntax() {
+        let parse = |color: &str| color.parse::<Color>().unwrap();
+
+        assert_eq!(parse("#f80"), Color::new(0xff, 0x88, 0x00));
+        assert_eq!(parse("#ff880080").alpha(), 128.0 / 255.0);
+        assert_eq!(parse("rgb(255, 136, 0)"), Color::new(0xff, 0x88, 0x00));
+        assert_eq!(parse("rgba(100% 0% 0% / 50%)").alpha(), 0.5);
+        assert_eq!(parse("rgba(0, 0, 255, 0.25)").alpha(), 0.25);
+        assert_eq!(parse("hsl(120deg, 100%, 25%)").to_hex(), "#008000");
+        assert_eq!(parse("hsla(0 100% 50% / 0.5)").to_hex(), "#ff0000");
+        assert_eq!(parse("oklch(62.8% 0.2577 29.23)").to_hex(), "#ff0000");
+        assert_eq!(parse("RebeccaPurple").to_hex(), "#663399");
+        assert_eq!(parse("transparent").alpha(), 0.0);
+
+        assert!(matches!(
+            "rgb(1, 2)".parse::<Color>(),
+            Err(ColorError::InvalidArguments(_))
+        ));
+        assert!(matches!(
+            "lab(50 0 0)".parse::<Color>(),
+            Err(ColorError::UnknownFunction(_))
+        ));
+        assert!(matches!(
+            "blurple".parse::<Color>(),
+            Err(ColorError::UnknownName(_))
+        ));
+    }
+
+    #[test]
+    fn alpha() {
+        let color = "rgba(255, 136, 0, 0.5)".parse::<Color>().unwrap();
+
+        assert_eq!(color.to_string(), "#ff880080");
+        assert_eq!(color.to_hex_rgba(), "#ff880080");
+        assert_eq!(color.to_hex_argb(), "#80ff8800");
+        assert_eq!(color.to_int_argb(), "0x80ff8800");
+        assert_eq!(color.to_rgba(), "rgba(255, 136, 0, 0.50)");
+        assert_eq!(color.lighten(0.5).alpha(), 0.5);
+        assert_eq!(color.with_alpha(1.0).to_string(), "#ff8800");
+    }
+
+    #[test]
+    fn perceptual() {
+        let yellow = Color::from_hex("#ffff00").unwrap();
+        let blue = Color::from_hex("#0000ff").unwrap();
+        let oklch = |color: Color| Oklch::from_color(color.value);
+
+        // Both hues lose the same perceived lightness and keep their hue.
+        let (darker_yellow, darker_blue) = (yellow.ok_darken(0.2), blue.ok_darken(0.2));
+        assert!((oklch(darker_yellow).l / oklch(yellow).l - 0.8).abs() < 0.01);
+        assert!((oklch(darker_blue).l / oklch(blue).l - 0.8).abs() < 0.01);
+        let hue = |color: Color| oklch(color).hue.into_positive_degrees();
+        assert!((hue(darker_blue) - hue(blue)).abs() < 1.0);
+
+        assert_eq!(blue.ok_lighten(1.0).to_hex(), "#ffffff");
+        assert!(blue.ok_saturate(-1.0).saturation() < 1e-4);
+        assert_eq!(blue.ok_shift_hue(360.0).to_hex(), "#0000ff");
+    }
+
+    #[test]
+    fn mix_and_blend() {
+        let black = Color::new(0, 0, 0);
+        let white = Color::new(255, 255, 255);
+
+        assert_eq!(black.mix(white, 0.0), black);
+        assert_eq!(black.mix(white, 1.0).to_hex(), "#ffffff");
+        assert_eq!(black.mix(white, 0.5).to_hex(), "#636363");
+
+        assert_eq!(white.with_alpha(0.5).blend(black).to_hex(), "#808080");
+        assert_eq!(white.blend(black), white);
+        assert_eq!(white.with_alpha(0.0).blend(black), black);
+    }
+
+    #[test]
+    fn polarity() {
+        let navy = Color::from_hex("#1a2b4c").unwrap();
+        let inverted = navy.invert_lightness();
+
+        assert!(navy.is_dark());
+        assert!(!inverted.is_dark());
+        assert!((inverted.hue() - navy.hue()).abs() < 2.0);
+        assert_eq!(inverted.invert_lightness().to_hex(), navy.to_hex());
+    }
+
+    #[test]
+    fn ramps() {
+        let black = Color::new(0, 0, 0);
+        let white = Color::new(255, 255, 255);
+
+        let gradient = black.gradient(white, 3);
+        assert_eq!(
+            gradient,
+            [black, black.mix(white, 0.5), black.mix(white, 1.0)]
+        );
+        assert_eq!(black.gradient(white, 1), [black]);
+        assert!(black.gradient(white, 0).is_empty());
+
+        let shades = Color::from_hex("#3366cc").unwrap().shades(4);
+        assert_eq!(shades.len(), 4);
+        assert!(shades
+            .windows(2)
+            .all(|pair| pair[0].lightness() < pair[1].lightness()));
+        assert!(black.shades(0).is_empty());
+    }
+
+    #[test]
+    fn contrast() {
+        let black = Color::new(0, 0, 0);
+        let white = Color::new(255, 255, 255);
+        assert!((black.contrast(white) - 21.0).abs() < 1e-4);
+        assert_eq!(white.contrast(white), 1.0);
+
+        let gray = Color::from_hex("#303030").unwrap();
+        let readable = gray.readable(black, 4.5);
+        assert!(readable.contrast(black) >= 4.5);
+        assert!(readable.lightness() > gray.lightness());
+        assert_eq!(white.readable(black, 4.5), white);
+        assert_eq!(gray.readable(gray, 30.0).to_hex(), "#ffffff");
+    }
+
+    #[test]
+    fn serde() {
+        use crate::yaml_parser::{self, YamlParser};
+
+        let color: Color = YamlParser::parse("'#336699'").unwrap();
+        assert_eq!(color, Color::new(0x33, 0x66, 0x99));
+        assert_eq!(yaml_parser::to_string(&color).unwrap(), "'#336699'\n");
+
+        let color: Color = YamlParser::parse("'rgba(51, 102, 153, 0.5)'").unwrap();
+        assert_eq!(yaml_parser::to_string(&color).unwrap(), "'#33669980'\n");
+
+        let err = YamlParser::parse::<Color>("'#12345'").err().unwrap();
+        assert!(err.to_string().contains("invalid color '#12345'"));
     }
 
     #[test]
@@ -1,7 +1,11 @@
-use crate::os::{self, Path, PathBuf, ReadError};
+use crate::os::{self, Path, PathBuf, ReadError, Symlinks, WriteOptions};
 use crate::renderer::Value;
+use crate::schedule::Schedule;
+use crate::template::Filter;
+use crate::theme::Variant;
 use crate::yaml_parser::{de, Deserialize, Deserializer, ParseError, YamlParser};
 use std::collections::HashMap;
+use std::env;
 use thiserror::Error;
 
 const CONFIG_FILE: &str = "config.yaml";
@@ -18,10 +22,111 @@ pub enum ConfigError {
 
 #[derive(Deserialize)]
 pub struct Template {
+    /// A file, or a directory or glob whose files are rendered to the same paths under the target.
     pub source: String,
 
     #[serde(deserialize_with = "resolve_path")]
     pub target: PathBuf,
+
+    #[serde(default)]
+    pub create_dirs: bool,
+
+    #[serde(default, deserialize_with = "parse_mode")]
+    pub mode: Option<u32>,
+
+    #[serde(default)]
+    pub symlinks: Symlinks,
+
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// Files that are copied as they are instead of rendered, on top of those that are not text.
+    #[serde(default)]
+    pub copy: Vec<String>,
+
+    /// The template is only rendered when this holds.
+    pub when: Option<Condition>,
+
+    /// Merged over the global variables for just this template.
+    #[serde(default)]
+    pub variables: HashMap<String, Value>,
+}
+
+/// Holds when every one of its checks does.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+    /// The host is one of these.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub hostname: Vec<String>,
+
+    /// The theme is light or dark.
+    pub variant: Option<Variant>,
+
+    /// Environment variables have these values.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+
+    /// Global variables have these values.
+    #[serde(default)]
+    pub variables: HashMap<String, Value>,
+}
+
+impl Condition {
+    pub fn holds(&self, is_dark: bool, variables: &HashMap<String, Value>) -> bool {
+        let hostname = || os::hostname().is_some_and(|name| self.hostname.contains(&name));
+        let variant = match self.variant {
+            Some(Variant::Dark) => is_dark,
+            Some(Variant::Light) => !is_dark,
+            None => true,
+        };
+
+        (self.hostname.is_empty() || hostname())
+            && variant
+            && self
+                .env
+                .iter()
+                .all(|(key, value)| env::var(key).is_ok_and(|var| var == *value))
+            && self
+                .variables
+                .iter()
+                .all(|(key, value)| variables.get(key) == Some(value))
+    }
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => Ok(vec![value]),
+        OneOrMany::Many(values) => Ok(values),
+    }
+}
+
+impl Template {
+    pub fn write_options(&self) -> WriteOptions {
+        WriteOptions {
+            create_dirs: self.create_dirs,
+            mode: self.mode,
+            symlinks: self.symlinks,
+        }
+    }
+
+    pub fn filter(&self) -> Filter<'_> {
+        Filter {
+            include: &self.include,
+            exclude: &self.exclude,
+            copy: &self.copy,
+        }
+    }
 }
 
 fn resolve_path<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
@@ -33,21 +138,44 @@ fn resolve_path<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D
     )))
 }
 
+/// Parses modes like `'0644'`. Unquoted modes are rejected, since YAML reads `0o644` as 420 and
+/// `644` as a decimal number, so the digits that were written cannot be recovered.
+fn parse_mode<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    let Value::String(mode) = Value::deserialize(deserializer)? else {
+        return Err(de::Error::custom(
+            "mode should be a quoted octal number like '0644'",
+        ));
+    };
+    let digits = mode.strip_prefix("0o").unwrap_or(&mode);
+
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(Some(mode)),
+        _ => Err(de::Error::custom(format!("invalid mode '{mode}'"))),
+    }
+}
+
 #[derive(Deserialize)]
 pub struct Config {
     pub theme: Option<String>,
     pub hooks: Option<Vec<String>>,
+    pub sequences: Option<bool>,
     pub variables: Option<HashMap<String, Value>>,
     pub templates: Option<Vec<Template>>,
+    pub schedule: Option<Schedule>,
+    /// How many generations of overwritten targets to keep for `rollback`.
+    pub backups: Option<usize>,
 }
 
 impl Config {
     pub fn new(config_dir: &Path) -> Result<Self, ConfigError> {
-        let config_file = Path::new(config_dir).join(CONFIG_FILE);
-        let contents = os::read_file(&config_file)?;
+        let contents = os::read_file(Self::file(config_dir))?;
 
         Ok(contents.as_str().try_into()?)
     }
+
+    pub fn file(config_dir: &Path) -> PathBuf {
+        config_dir.join(CONFIG_FILE)
+    }
 }
 
 impl TryFrom<&str> for Config {
@@ -73,6 +201,7 @@ mod tests {
             &config_file,
             "
 theme: monokai
+backups: 5
 
 hooks:
   - set-wallpaper.sh
@@ -80,11 +209,32 @@ hooks:
 variables:
   alpha: 0.1
 
+schedule:
+  latitude: 52.52
+  longitude: 13.40
+  themes:
+    - from: sunrise
+      theme: monokai-light
+    - from: sunset-00:30
+      theme: monokai
+
 templates:
   - source: dunstrc
     target: ~/.config/dunst/dunstrc
   - source: colors.rasi
     target: ~/.config/rofi/colors.rasi
+    create_dirs: true
+    mode: '0600'
+    symlinks: replace
+  - source: gtk/**
+    target: ~/.config/gtk-3.0
+    exclude: ['*.md']
+    copy: [assets/*]
+    when:
+      hostname: [laptop, desktop]
+      variant: dark
+    variables:
+      alpha: 0.9
 ",
         )
         .unwrap();
@@ -96,17 +246,71 @@ templates:
         let variables = config.variables.unwrap_or_default();
         let hooks = config.hooks.unwrap_or_default();
 
-        let dunstrc = templates.get(0).unwrap();
+        let dunstrc = templates.first().unwrap();
         assert_eq!(dunstrc.source, "dunstrc");
         assert_eq!(dunstrc.target, homedir.join(".config/dunst/dunstrc"));
 
         let rofi_colors = templates.get(1).unwrap();
         assert_eq!(rofi_colors.source, "colors.rasi");
         assert_eq!(rofi_colors.target, homedir.join(".config/rofi/colors.rasi"));
+        assert!(!dunstrc.create_dirs);
+        assert_eq!(dunstrc.symlinks, Symlinks::Follow);
+        assert!(rofi_colors.create_dirs);
+        assert_eq!(rofi_colors.mode, Some(0o600));
+        assert_eq!(rofi_colors.symlinks, Symlinks::Replace);
+        assert!(rofi_colors.include.is_empty());
+
+        let gtk = templates.get(2).unwrap();
+        assert_eq!(gtk.source, "gtk/**");
+        assert_eq!(gtk.exclude, ["*.md"]);
+        assert_eq!(gtk.copy, ["assets/*"]);
+        let when = gtk.when.as_ref().unwrap();
+        assert_eq!(when.hostname, ["laptop", "desktop"]);
+        assert_eq!(when.variant, Some(Variant::Dark));
+        assert_eq!(gtk.variables.get("alpha").unwrap(), 0.9);
+        assert!(dunstrc.when.is_none());
 
         assert_eq!(variables.get("alpha").unwrap(), 0.1);
         assert_eq!(config.theme.unwrap(), "monokai");
+        assert_eq!(config.backups, Some(5));
+
+        let template = |mode: &str| {
+            YamlParser::parse::<Template>(&format!("{{source: a, target: /a, mode: {mode}}}"))
+                .map(|template| template.mode)
+        };
+        assert_eq!(template("'644'").unwrap(), Some(0o644));
+        assert_eq!(template("'0o755'").unwrap(), Some(0o755));
+        assert!(template("644").is_err());
+        assert!(template("0o600").is_err());
+        assert!(template("'0999'").is_err());
+
+        assert_eq!(hooks.first().unwrap(), "set-wallpaper.sh");
+
+        let schedule = config.schedule.unwrap();
+        assert_eq!(schedule.latitude, Some(52.52));
+        assert_eq!(schedule.themes[1].theme, "monokai");
+    }
+
+    #[test]
+    fn condition() {
+        let condition = |yaml: &str| YamlParser::parse::<Condition>(yaml).unwrap();
+        let variables = HashMap::from([("bar".to_string(), Value::from("waybar"))]);
+
+        assert!(Condition::default().holds(false, &variables));
+        assert!(condition("variant: dark").holds(true, &variables));
+        assert!(!condition("variant: dark").holds(false, &variables));
+        assert!(condition("variables: {bar: waybar}").holds(true, &variables));
+        assert!(!condition("variables: {bar: polybar}").holds(true, &variables));
+        assert!(!condition("variables: {missing: 1}").holds(true, &variables));
+
+        let hostname = os::hostname().unwrap();
+        assert!(condition(&format!("hostname: {hostname}")).holds(true, &variables));
+        assert!(!condition("hostname: [no-such-host]").holds(true, &variables));
+
+        let path = env::var("PATH").unwrap();
+        assert!(condition(&format!("env: {{PATH: '{path}'}}")).holds(true, &variables));
+        assert!(!condition("env: {RSWAL_UNSET_VARIABLE: x}").holds(true, &variables));
 
-        assert_eq!(hooks.get(0).unwrap(), "set-wallpaper.sh");
+        assert!(YamlParser::parse::<Condition>("host: laptop").is_err());
     }
 }
@@ -0,0 +1,194 @@
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// A unified diff between two texts, or an empty string if they are the same. Lines keep their
+/// newline, so that a missing one at the end of a text shows up as a change like in `diff -u`.
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let old = old.split_inclusive('\n').collect::<Vec<&str>>();
+    let new = new.split_inclusive('\n').collect::<Vec<&str>>();
+    let ops = diff(&old, &new);
+
+    let changes = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, (op, _))| *op != Op::Equal)
+        .map(|(index, _)| index)
+        .collect::<Vec<usize>>();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // Changes whose context overlaps end up in the same hunk.
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for change in changes {
+        let (start, end) = (
+            change.saturating_sub(CONTEXT),
+            (change + CONTEXT + 1).min(ops.len()),
+        );
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let count = |ops: &[(Op, &str)], skip: Op| ops.iter().filter(|(op, _)| *op != skip).count();
+    let range = |start: usize, len: usize| match len {
+        0 => format!("{start},0"),
+        _ => format!("{},{len}", start + 1),
+    };
+
+    let mut diff = format!("--- {old_name}\n+++ {new_name}\n");
+    for (start, end) in hunks {
+        let (before, hunk) = (&ops[..start], &ops[start..end]);
+        diff += &format!(
+            "@@ -{} +{} @@\n",
+            range(count(before, Op::Insert), count(hunk, Op::Insert)),
+            range(count(before, Op::Delete), count(hunk, Op::Delete)),
+        );
+
+        for (op, line) in hunk {
+            let prefix = match op {
+                Op::Equal => ' ',
+                Op::Delete => '-',
+                Op::Insert => '+',
+            };
+            diff += &match line.ends_with('\n') {
+                true => format!("{prefix}{line}"),
+                false => format!("{prefix}{line}\n\\ No newline at end of file\n"),
+            };
+        }
+    }
+
+    diff
+}
+
+/// The edit script between two sequences of lines, from their longest common subsequence.
+fn diff<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Op, &'a str)> {
+    let mut ops = Vec::with_capacity(old.len().max(new.len()));
+    push_diff(old, new, &mut ops);
+
+    ops
+}
+
+/// Hirschberg's algorithm: splits `old` in half and `new` where the longest common subsequences
+/// of both halves add up to the longest, so that only a row of lengths is kept at a time.
+fn push_diff<'a>(old: &[&'a str], new: &[&'a str], ops: &mut Vec<(Op, &'a str)>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    ops.extend(old[..prefix].iter().map(|line| (Op::Equal, *line)));
+    match (old_middle, new_middle) {
+        ([], _) => ops.extend(new_middle.iter().map(|line| (Op::Insert, *line))),
+        (_, []) => ops.extend(old_middle.iter().map(|line| (Op::Delete, *line))),
+        ([line], _) => match new_middle.iter().position(|other| other == line) {
+            Some(index) => {
+                ops.extend(new_middle[..index].iter().map(|line| (Op::Insert, *line)));
+                ops.push((Op::Equal, line));
+                ops.extend(
+                    new_middle[index + 1..]
+                        .iter()
+                        .map(|line| (Op::Insert, *line)),
+                );
+            }
+            None => {
+                ops.push((Op::Delete, line));
+                ops.extend(new_middle.iter().map(|line| (Op::Insert, *line)));
+            }
+        },
+        _ => {
+            let half = old_middle.len() / 2;
+            let forward = lengths(old_middle[..half].iter(), new_middle.iter());
+            let backward = lengths(old_middle[half..].iter().rev(), new_middle.iter().rev());
+            let split = (0..=new_middle.len())
+                .rev()
+                .max_by_key(|&j| forward[j] + backward[new_middle.len() - j])
+                .unwrap_or_default();
+
+            push_diff(&old_middle[..half], &new_middle[..split], ops);
+            push_diff(&old_middle[half..], &new_middle[split..], ops);
+        }
+    }
+    ops.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| (Op::Equal, *line)),
+    );
+}
+
+/// The length of the longest common subsequence of `old` and each prefix of `new`.
+fn lengths<'a, 'b>(
+    old: impl Iterator<Item = &'a &'b str>,
+    new: impl Iterator<Item = &'a &'b str> + Clone,
+) -> Vec<usize>
+where
+    'b: 'a,
+{
+    let mut row = vec![0; new.clone().count() + 1];
+    for line in old {
+        let mut diagonal = 0;
+        for (j, other) in new.clone().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = match line == other {
+                true => diagonal + 1,
+                false => above.max(row[j]),
+            };
+            diagonal = above;
+        }
+    }
+
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn unified() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nk\nl\n";
+
+        assert_eq!(
+            super::unified(old, new, "old", "new"),
+            "--- old
++++ new
+@@ -1,5 +1,5 @@
+ a
+-b
++B
+ c
+ d
+ e
+@@ -7,5 +7,5 @@
+ g
+ h
+ i
+-j
+ k
++l
+"
+        );
+        assert_eq!(super::unified(old, old, "old", "new"), "");
+        assert_eq!(
+            super::unified("", "x\n", "old", "new"),
+            "--- old\n+++ new\n@@ -0,0 +1,1 @@\n+x\n"
+        );
+        assert_eq!(
+            super::unified("a\nb\n", "a\nb", "old", "new"),
+            "--- old\n+++ new\n@@ -1,2 +1,2 @@\n a\n-b\n+b\n\\ No newline at end of file\n"
+        );
+    }
+}
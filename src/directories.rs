@@ -1,25 +1,41 @@
-use crate::os::{Path, PathBuf};
+use crate::os::{self, Path, PathBuf};
+use std::env;
 
+const BINARY_NAME: &str = env!("CARGO_PKG_NAME");
 const TEMPLATE_DIR: &str = "templates";
+const PARTIAL_DIR: &str = "partials";
 const THEME_DIR: &str = "themes";
 const HOOK_DIR: &str = "hooks";
 
 pub struct Directories {
     pub template_dir: PathBuf,
+    pub partial_dir: PathBuf,
     pub theme_dir: PathBuf,
     pub hook_dir: PathBuf,
+    pub cache_dir: PathBuf,
 }
 
 impl Directories {
     pub fn new(config_dir: &Path) -> Self {
         Directories {
             template_dir: config_dir.join(TEMPLATE_DIR),
+            partial_dir: config_dir.join(TEMPLATE_DIR).join(PARTIAL_DIR),
             theme_dir: config_dir.join(THEME_DIR),
             hook_dir: config_dir.join(HOOK_DIR),
+            cache_dir: cache_dir(),
         }
     }
 }
 
+fn cache_dir() -> PathBuf {
+    let cache_dir = env::var("XDG_CACHE_HOME").unwrap_or("~/.cache".to_string());
+    let cache_dir = Path::new(&cache_dir);
+
+    os::resolve_path(cache_dir)
+        .unwrap_or(cache_dir.to_path_buf())
+        .join(BINARY_NAME)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -34,6 +50,10 @@ mod tests {
             dirs.template_dir.to_str().unwrap(),
             config_dir.to_string() + TEMPLATE_DIR
         );
+        assert_eq!(
+            dirs.partial_dir.to_str().unwrap(),
+            config_dir.to_string() + TEMPLATE_DIR + "/" + PARTIAL_DIR
+        );
         assert_eq!(
             dirs.theme_dir.to_str().unwrap(),
             config_dir.to_string() + THEME_DIR
@@ -42,5 +62,6 @@ mod tests {
             dirs.hook_dir.to_str().unwrap(),
             config_dir.to_string() + HOOK_DIR
         );
+        assert!(dirs.cache_dir.ends_with(BINARY_NAME));
     }
 }
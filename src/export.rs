@@ -0,0 +1,285 @@
+use crate::color::Color;
+use crate::os::WriteError;
+use crate::renderer::{context, RenderError, Renderer, Serialize, Serializer};
+use crate::theme::{SpecialColors, Theme};
+use crate::yaml_parser::{self, ParseError};
+use clap::ValueEnum;
+use serde::ser::SerializeMap;
+use thiserror::Error;
+
+// These formats have no alpha channel, so their colors are always written as `#rrggbb`. The name
+// is quoted as a JSON string, which YAML reads as a double-quoted scalar.
+const BASE16: &str = r#"scheme: {{{quoted_name}}}
+author: "rswal"
+{{#each base16}}
+{{@key}}: "{{strip (toHex this)}}"
+{{/each}}
+"#;
+
+const ALACRITTY: &str = r#"[colors.primary]
+background = '{{toHex colors.special.background}}'
+foreground = '{{toHex colors.special.foreground}}'
+
+[colors.cursor]
+text = '{{toHex colors.special.background}}'
+cursor = '{{toHex colors.special.cursor}}'
+
+[colors.normal]
+black = '{{toHex colors.normal.black}}'
+red = '{{toHex colors.normal.red}}'
+green = '{{toHex colors.normal.green}}'
+yellow = '{{toHex colors.normal.yellow}}'
+blue = '{{toHex colors.normal.blue}}'
+magenta = '{{toHex colors.normal.magenta}}'
+cyan = '{{toHex colors.normal.cyan}}'
+white = '{{toHex colors.normal.white}}'
+
+[colors.bright]
+black = '{{toHex colors.bright.black}}'
+red = '{{toHex colors.bright.red}}'
+green = '{{toHex colors.bright.green}}'
+yellow = '{{toHex colors.bright.yellow}}'
+blue = '{{toHex colors.bright.blue}}'
+magenta = '{{toHex colors.bright.magenta}}'
+cyan = '{{toHex colors.bright.cyan}}'
+white = '{{toHex colors.bright.white}}'
+"#;
+
+const KITTY: &str = r#"background {{toHex colors.special.background}}
+foreground {{toHex colors.special.foreground}}
+cursor {{toHex colors.special.cursor}}
+{{#each ansi}}
+color{{@index}} {{toHex this}}
+{{/each}}
+"#;
+
+const XRESOURCES: &str = r#"*.background: {{toHex colors.special.background}}
+*.foreground: {{toHex colors.special.foreground}}
+*.cursorColor: {{toHex colors.special.cursor}}
+{{#each ansi}}
+*.color{{@index}}: {{toHex this}}
+{{/each}}
+"#;
+
+const CSS: &str = r#":root {
+  --background: {{colors.special.background}};
+  --foreground: {{colors.special.foreground}};
+  --cursor: {{colors.special.cursor}};
+{{#each ansi}}
+  --color{{@index}}: {{this}};
+{{/each}}
+}
+"#;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("serialization failed -> {0}")]
+    Serialize(#[from] ParseError),
+
+    #[error("serialization failed -> {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("render failed -> {0}")]
+    Render(#[from] RenderError),
+
+    #[error("write failed -> {0}")]
+    Write(#[from] WriteError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+    /// A theme which rswal can load.
+    Yaml,
+    /// A base16 scheme.
+    Base16,
+    /// Alacritty's TOML configuration.
+    Alacritty,
+    /// Kitty's configuration.
+    Kitty,
+    /// X resources.
+    Xresources,
+    /// A JSON palette like pywal's `colors.json`.
+    Json,
+    /// CSS custom properties.
+    Css,
+}
+
+impl ExportFormat {
+    fn template(self) -> Option<&'static str> {
+        match self {
+            ExportFormat::Yaml | ExportFormat::Json => None,
+            ExportFormat::Base16 => Some(BASE16),
+            ExportFormat::Alacritty => Some(ALACRITTY),
+            ExportFormat::Kitty => Some(KITTY),
+            ExportFormat::Xresources => Some(XRESOURCES),
+            ExportFormat::Css => Some(CSS),
+        }
+    }
+}
+
+/// pywal's `colors.json`, with the ANSI colors as `color0` to `color15` in order.
+#[derive(Serialize)]
+struct JsonPalette<'a> {
+    name: &'a str,
+    special: &'a SpecialColors,
+    #[serde(serialize_with = "serialize_ansi")]
+    colors: &'a [Color],
+}
+
+fn serialize_ansi<S: Serializer>(colors: &&[Color], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(colors.len()))?;
+    for (index, color) in colors.iter().enumerate() {
+        map.serialize_entry(&format!("color{index}"), color)?;
+    }
+
+    map.end()
+}
+
+/// Converts a theme into another color scheme format using the built-in templates.
+pub fn export(format: ExportFormat, name: &str, theme: &Theme) -> Result<String, ExportError> {
+    let ansi = theme
+        .normal
+        .colors()
+        .into_iter()
+        .chain(theme.bright.colors())
+        .collect::<Vec<Color>>();
+    let template = match (format, format.template()) {
+        (_, Some(template)) => template,
+        (ExportFormat::Json, None) => {
+            let palette = JsonPalette {
+                name,
+                special: &theme.special,
+                colors: &ansi,
+            };
+
+            return Ok(serde_json::to_string_pretty(&palette)? + "\n");
+        }
+        (_, None) => return Ok(yaml_parser::to_string(theme)?),
+    };
+
+    let context = context!({
+        "name": name,
+        "quoted_name": serde_json::to_string(name)?,
+        "colors": theme.context(),
+        "ansi": ansi,
+        "base16": theme.base16(),
+    });
+    let renderer = Renderer::new(&context);
+
+    Ok(renderer.render(template)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{self, Format};
+
+    fn theme() -> Theme {
+        Theme::from_palette(&[
+            Color::from_hex("#101010").unwrap(),
+            Color::from_hex("#e0e0e0").unwrap(),
+            Color::from_hex("#cc3333").unwrap(),
+            Color::from_hex("#33cc33").unwrap(),
+            Color::from_hex("#3333cc").unwrap(),
+        ])
+    }
+
+    #[test]
+    fn round_trip() {
+        let theme = theme();
+        let formats = [
+            (ExportFormat::Yaml, Format::Yaml),
+            (ExportFormat::Alacritty, Format::Alacritty),
+            (ExportFormat::Kitty, Format::Kitty),
+            (ExportFormat::Xresources, Format::Xresources),
+        ];
+
+        for (export_format, import_format) in formats {
+            let exported = export(export_format, "test", &theme).unwrap();
+            let imported = import::import(import_format, &exported).unwrap();
+
+            assert_eq!(
+                yaml_parser::to_string(&imported).unwrap(),
+                yaml_parser::to_string(&theme).unwrap(),
+                "{export_format:?}"
+            );
+        }
+
+        let exported = export(ExportFormat::Base16, "test", &theme).unwrap();
+        assert!(exported.starts_with("scheme: \"test\"\n"));
+        let name = r#"Black & "White" \ Grey"#;
+        let exported = export(ExportFormat::Base16, name, &theme).unwrap();
+        let scheme: serde_yaml::Value = serde_yaml::from_str(&exported).unwrap();
+        assert_eq!(scheme["scheme"], name);
+        let imported = import::import(Format::Base16, &exported).unwrap();
+        assert_eq!(
+            yaml_parser::to_string(&imported.base).unwrap(),
+            yaml_parser::to_string(&theme.base16()).unwrap()
+        );
+    }
+
+    #[test]
+    fn translucent() {
+        let opaque = theme();
+        let background = opaque.special.background;
+        let mut theme = theme();
+        theme.special.background = background.with_alpha(0.5);
+        theme.normal.red = theme.normal.red.with_alpha(0.5);
+        let formats = [
+            (ExportFormat::Alacritty, Format::Alacritty),
+            (ExportFormat::Kitty, Format::Kitty),
+            (ExportFormat::Xresources, Format::Xresources),
+        ];
+
+        // Formats without an alpha channel drop it rather than writing `#rrggbbaa`.
+        for (export_format, import_format) in formats {
+            let exported = export(export_format, "test", &theme).unwrap();
+            let imported = import::import(import_format, &exported).unwrap();
+
+            assert_eq!(
+                yaml_parser::to_string(&imported).unwrap(),
+                yaml_parser::to_string(&opaque).unwrap(),
+                "{export_format:?}"
+            );
+        }
+
+        let exported = export(ExportFormat::Base16, "test", &theme).unwrap();
+        assert!(exported.contains(&format!("base00: \"{}\"\n", &background.to_hex()[1..])));
+        assert!(import::import(Format::Base16, &exported).is_ok());
+
+        // Formats with one keep it.
+        let exported = export(ExportFormat::Css, "test", &theme).unwrap();
+        assert!(exported.contains(&format!(
+            "  --background: {};\n",
+            theme.special.background.to_hex_rgba()
+        )));
+        let exported = export(ExportFormat::Json, "test", &theme).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&exported).unwrap();
+        assert_eq!(json["colors"]["color1"], theme.normal.red.to_hex_rgba());
+    }
+
+    #[test]
+    fn palettes() {
+        let theme = theme();
+
+        let exported = export(ExportFormat::Json, "test", &theme).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&exported).unwrap();
+        assert_eq!(json["name"], "test");
+        assert_eq!(
+            json["special"]["background"],
+            theme.special.background.to_hex()
+        );
+        assert_eq!(json["colors"]["color1"], theme.normal.red.to_hex());
+        assert_eq!(json["colors"]["color15"], theme.bright.white.to_hex());
+        assert!(exported.find("\"color2\"") < exported.find("\"color10\""));
+
+        let name = r#"Black & "White" \ Grey"#;
+        let exported = export(ExportFormat::Json, name, &theme).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&exported).unwrap();
+        assert_eq!(json["name"], name);
+
+        let exported = export(ExportFormat::Css, "test", &theme).unwrap();
+        assert!(exported.starts_with(":root {\n"));
+        assert!(exported.contains(&format!("  --color4: {};\n", theme.normal.blue.to_hex())));
+    }
+}
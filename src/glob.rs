@@ -0,0 +1,86 @@
+use crate::os::PathBuf;
+
+/// Whether the pattern has wildcards, rather than naming a path as it is.
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// The leading directories of the pattern, which have no wildcards.
+pub fn base(pattern: &str) -> PathBuf {
+    pattern
+        .split('/')
+        .take_while(|component| !is_glob(component))
+        .take(pattern.split('/').count() - 1)
+        .collect()
+}
+
+/// Matches a `/` separated path, where `*` and `?` match any characters or one character within a
+/// component and `**` matches any number of components.
+pub fn matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.split('/').collect::<Vec<&str>>();
+    let path = path.split('/').collect::<Vec<&str>>();
+
+    matches_components(&pattern, &path)
+}
+
+/// Whether any of the patterns matches the path. Patterns without a `/` match the file name, so
+/// that `*.png` matches images in every directory.
+pub fn matches_any(patterns: &[String], path: &str) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+
+    patterns.iter().any(|pattern| match pattern.contains('/') {
+        true => matches(pattern, path),
+        false => matches(pattern, file_name),
+    })
+}
+
+fn matches_components(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| matches_components(rest, &path[skip..])),
+        Some((component, rest)) => path.split_first().is_some_and(|(name, path)| {
+            let component = component.chars().collect::<Vec<char>>();
+            let name = name.chars().collect::<Vec<char>>();
+
+            matches_component(&component, &name) && matches_components(rest, path)
+        }),
+    }
+}
+
+fn matches_component(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| matches_component(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && matches_component(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && matches_component(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching() {
+        assert!(matches("gtk/*.css", "gtk/gtk.css"));
+        assert!(!matches("gtk/*.css", "gtk/dark/gtk.css"));
+        assert!(matches("gtk/**", "gtk/dark/gtk.css"));
+        assert!(matches("**/*.css", "gtk.css"));
+        assert!(matches("gtk/**/?tk.css", "gtk/a/b/gtk.css"));
+        assert!(!matches("gtk/**", "rofi/theme.rasi"));
+
+        let patterns = ["*.png".to_string(), "dark/*".to_string()];
+        assert!(matches_any(&patterns, "assets/check.png"));
+        assert!(matches_any(&patterns, "dark/gtk.css"));
+        assert!(!matches_any(&patterns, "light/gtk.css"));
+    }
+
+    #[test]
+    fn globs() {
+        assert!(is_glob("gtk/**"));
+        assert!(!is_glob("gtk/gtk.css"));
+        assert_eq!(base("gtk/**"), PathBuf::from("gtk"));
+        assert_eq!(base("gtk/*/a.css"), PathBuf::from("gtk"));
+        assert_eq!(base("*.css"), PathBuf::new());
+    }
+}
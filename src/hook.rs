@@ -32,6 +32,10 @@ impl<'a> Hook<'a> {
         }
     }
 
+    pub fn exists(&self) -> bool {
+        self.path.is_file()
+    }
+
     pub fn execute<K: ToString, V: ToString>(
         &self,
         variables: &[(K, V)],
@@ -89,7 +93,10 @@ echo ${NAME}",
         .unwrap();
         fs::set_permissions(&hook_file, fs::Permissions::from_mode(0o755)).unwrap();
 
-        let hook = Hook::new(hook, &hook_dir_path);
+        let hook = Hook::new(hook, hook_dir_path);
+        assert!(hook.exists());
+        assert!(!Hook::new("missing.sh", hook_dir_path).exists());
+
         let variables = vec![("name", "John")];
         assert_eq!(hook.execute(&variables).unwrap(), "John\n");
 
@@ -107,7 +114,7 @@ echo ${FIRST_NAME}",
     #[test]
     fn path() {
         let hook_dir_path = Path::new("/root/.config/foo/hooks");
-        let hook = Hook::new("hook", &hook_dir_path);
+        let hook = Hook::new("hook", hook_dir_path);
 
         assert_eq!(hook.path, Path::new("/root/.config/foo/hooks/hook"));
     }
@@ -0,0 +1,237 @@
+mod alacritty;
+mod base16;
+mod iterm;
+mod kitty;
+mod windows_terminal;
+mod xresources;
+
+use crate::color::Color;
+use crate::theme::{AnsiColors, SpecialColors, Theme};
+use crate::yaml_parser::ParseError;
+use clap::ValueEnum;
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("parse failed -> {0}")]
+    Parse(#[from] ParseError),
+
+    #[error("invalid syntax: {0}")]
+    Syntax(String),
+
+    #[error("missing color '{0}'")]
+    MissingColor(String),
+
+    #[error("invalid color '{0}': {1}")]
+    InvalidColor(String, String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Format {
+    Yaml,
+    Base16,
+    Alacritty,
+    Kitty,
+    Xresources,
+    WindowsTerminal,
+    Iterm,
+}
+
+// Longer suffixes come first so that `.base16.yaml` is not mistaken for a plain YAML theme.
+const SUFFIXES: [(&str, Format); 12] = [
+    (".base16.yaml", Format::Base16),
+    (".base24.yaml", Format::Base16),
+    (".base16.yml", Format::Base16),
+    (".base24.yml", Format::Base16),
+    (".yaml", Format::Yaml),
+    (".yml", Format::Yaml),
+    (".toml", Format::Alacritty),
+    (".conf", Format::Kitty),
+    (".Xresources", Format::Xresources),
+    (".xresources", Format::Xresources),
+    (".json", Format::WindowsTerminal),
+    (".itermcolors", Format::Iterm),
+];
+
+impl Format {
+    /// Splits the file name of a theme into the theme name and its format.
+    pub fn from_file_name(file_name: &str) -> Option<(&str, Format)> {
+        SUFFIXES.iter().find_map(|(suffix, format)| {
+            file_name
+                .strip_suffix(suffix)
+                .filter(|name| !name.is_empty())
+                .map(|name| (name, *format))
+        })
+    }
+
+    /// Refines the format guessed from the file name with the contents of the file.
+    pub fn detect(self, contents: &str) -> Self {
+        match self {
+            Format::Yaml if base16::is_scheme(contents) => Format::Base16,
+            format => format,
+        }
+    }
+}
+
+/// Converts a color scheme in a foreign format into a theme.
+pub fn import(format: Format, contents: &str) -> Result<Theme, ImportError> {
+    match format {
+        Format::Yaml => Ok(Theme::try_from(contents)?),
+        Format::Base16 => base16::import(contents),
+        Format::Alacritty => alacritty::import(contents),
+        Format::Kitty => kitty::import(contents),
+        Format::Xresources => xresources::import(contents),
+        Format::WindowsTerminal => windows_terminal::import(contents),
+        Format::Iterm => iterm::import(contents),
+    }
+}
+
+/// Collects the colors of a terminal scheme, keyed by `background`, `foreground`, `cursor` and
+/// `color0` through `color15` like most terminals do.
+#[derive(Default)]
+struct Colors {
+    colors: HashMap<String, Color>,
+}
+
+impl Colors {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), ImportError> {
+        let color = parse_color(value).ok_or(ImportError::InvalidColor(
+            key.to_string(),
+            value.to_string(),
+        ))?;
+        self.insert(key, color);
+
+        Ok(())
+    }
+
+    fn insert(&mut self, key: &str, color: Color) {
+        self.colors.insert(key.to_string(), color);
+    }
+
+    fn get(&self, key: &str) -> Result<Color, ImportError> {
+        self.colors
+            .get(key)
+            .copied()
+            .ok_or(ImportError::MissingColor(key.to_string()))
+    }
+
+    fn ansi(&self, offset: usize) -> Result<AnsiColors, ImportError> {
+        let color = |index: usize| self.get(&format!("color{}", index + offset));
+
+        Ok(AnsiColors {
+            black: color(0)?,
+            red: color(1)?,
+            green: color(2)?,
+            yellow: color(3)?,
+            blue: color(4)?,
+            magenta: color(5)?,
+            cyan: color(6)?,
+            white: color(7)?,
+        })
+    }
+
+    fn into_theme(self) -> Result<Theme, ImportError> {
+        let foreground = self.get("foreground")?;
+        let special = SpecialColors {
+            background: self.get("background")?,
+            cursor: self.get("cursor").unwrap_or(foreground),
+            foreground,
+        };
+
+        Ok(Theme {
+            special,
+            normal: self.ansi(0)?,
+            bright: self.ansi(8)?,
+            base: BTreeMap::new(),
+        })
+    }
+}
+
+fn is_color_key(key: &str) -> bool {
+    match key.strip_prefix("color") {
+        Some(index) => index.parse::<u8>().is_ok_and(|index| index < 16),
+        None => matches!(key, "background" | "foreground" | "cursor"),
+    }
+}
+
+/// Parses the hex notations found in terminal configs: `#rrggbb`, `0xrrggbb`, `rrggbb`, `#rgb`
+/// and X11's `rgb:rr/gg/bb`.
+fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+    let hex = match value.strip_prefix("rgb:") {
+        Some(channels) => channels
+            .split('/')
+            .map(|channel| channel.get(..2))
+            .collect::<Option<String>>()?,
+        None => value
+            .strip_prefix('#')
+            .or(value.strip_prefix("0x"))
+            .unwrap_or(value)
+            .to_string(),
+    };
+
+    Color::from_hex(&format!("#{hex}"))
+        .or_else(|_| value.parse())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names() {
+        assert_eq!(
+            Format::from_file_name("monokai.yaml"),
+            Some(("monokai", Format::Yaml))
+        );
+        assert_eq!(
+            Format::from_file_name("ocean.base16.yaml"),
+            Some(("ocean", Format::Base16))
+        );
+        assert_eq!(
+            Format::from_file_name("ocean.base24.yml"),
+            Some(("ocean", Format::Base16))
+        );
+        assert_eq!(
+            Format::from_file_name("dracula.itermcolors"),
+            Some(("dracula", Format::Iterm))
+        );
+        assert_eq!(
+            Format::from_file_name("nord.Xresources"),
+            Some(("nord", Format::Xresources))
+        );
+        assert_eq!(Format::from_file_name(".yaml"), None);
+        assert_eq!(Format::from_file_name("README.md"), None);
+
+        assert_eq!(Format::Yaml.detect("base00: '000000'"), Format::Base16);
+        assert_eq!(Format::Yaml.detect("special: {}"), Format::Yaml);
+    }
+
+    #[test]
+    fn colors() {
+        let hex = |value: &str| parse_color(value).map(Color::to_hex);
+
+        assert_eq!(hex("#1d1f21").unwrap(), "#1d1f21");
+        assert_eq!(hex("'0x1D1F21'").unwrap(), "#1d1f21");
+        assert_eq!(hex("1d1f21").unwrap(), "#1d1f21");
+        assert_eq!(hex("#abc").unwrap(), "#aabbcc");
+        assert_eq!(hex("rgb:1d/1f/21").unwrap(), "#1d1f21");
+        assert_eq!(hex("rgb:1d1d/1f1f/2121").unwrap(), "#1d1f21");
+        assert_eq!(hex("blue").unwrap(), "#0000ff");
+        assert!(hex("bleu").is_none());
+
+        let mut colors = Colors::default();
+        colors.set("background", "#000000").unwrap();
+        colors.set("foreground", "#ffffff").unwrap();
+        (0..16).for_each(|index| colors.set(&format!("color{index}"), "#123456").unwrap());
+        let theme = colors.into_theme().unwrap();
+        assert_eq!(theme.special.cursor.to_hex(), "#ffffff");
+
+        assert!(matches!(
+            Colors::default().set("color0", "nope"),
+            Err(ImportError::InvalidColor(key, _)) if key == "color0"
+        ));
+    }
+}
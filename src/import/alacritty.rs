@@ -0,0 +1,108 @@
+use super::{Colors, ImportError};
+use crate::theme::Theme;
+use toml::{Table, Value};
+
+const ANSI_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// Imports the `[colors.*]` tables of an Alacritty configuration.
+pub fn import(contents: &str) -> Result<Theme, ImportError> {
+    let config: Table = contents
+        .parse()
+        .map_err(|err: toml::de::Error| ImportError::Syntax(err.message().to_string()))?;
+    // Standalone theme files sometimes leave out the `colors` table.
+    let colors_table = match config.get("colors") {
+        Some(Value::Table(colors)) => colors,
+        _ => &config,
+    };
+    let get = |table: &str, key: &str| {
+        colors_table
+            .get(table)
+            .and_then(|table| table.get(key))
+            .and_then(Value::as_str)
+    };
+
+    let mut colors = Colors::default();
+    let special = [
+        ("background", "primary", "background"),
+        ("foreground", "primary", "foreground"),
+        ("cursor", "cursor", "cursor"),
+    ];
+    for (key, table, name) in special {
+        // The cursor may take the colors of the cell under it instead, which leaves it to fall
+        // back to the foreground.
+        match get(table, name) {
+            Some("CellForeground" | "CellBackground") | None => {}
+            Some(value) => colors.set(key, value)?,
+        }
+    }
+    for (offset, table) in [(0, "normal"), (8, "bright")] {
+        for (index, name) in ANSI_NAMES.iter().enumerate() {
+            if let Some(value) = get(table, name) {
+                colors.set(&format!("color{}", index + offset), value)?;
+            }
+        }
+    }
+
+    colors.into_theme()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import() {
+        let config = "
+[colors.primary]
+background = '#1d1f21'
+foreground = '#c5c8c6'
+
+[colors.cursor]
+text = '#1d1f21'
+cursor = '#ffffff'
+
+[colors.normal]
+black = '#282a2e'
+red = '#a54242'
+green = '#8c9440'
+yellow = '#de935f'
+blue = '#5f819d'
+magenta = '#85678f'
+cyan = '#5e8d87'
+white = '#707880'
+
+[colors.bright]
+black = '0x373b41'
+red = '#cc6666'
+green = '#b5bd68'
+yellow = '#f0c674'
+blue = '#81a2be'
+magenta = '#b294bb'
+cyan = '#8abeb7'
+white = '#c5c8c6'
+";
+        let theme = super::import(config).unwrap();
+
+        assert_eq!(theme.special.background.to_hex(), "#1d1f21");
+        assert_eq!(theme.special.cursor.to_hex(), "#ffffff");
+        assert_eq!(theme.normal.magenta.to_hex(), "#85678f");
+        assert_eq!(theme.bright.black.to_hex(), "#373b41");
+
+        for cell in ["CellForeground", "CellBackground"] {
+            let config = config.replace("cursor = '#ffffff'", &format!("cursor = '{cell}'"));
+            let theme = super::import(&config).unwrap();
+            assert_eq!(theme.special.cursor.to_hex(), "#c5c8c6");
+        }
+
+        assert!(matches!(
+            super::import("[colors.primary]\nbackground = '#000000'"),
+            Err(ImportError::MissingColor(_))
+        ));
+        assert!(matches!(
+            super::import("[colors"),
+            Err(ImportError::Syntax(_))
+        ));
+    }
+}
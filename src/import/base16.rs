@@ -0,0 +1,173 @@
+use super::ImportError;
+use crate::color::Color;
+use crate::renderer::Value;
+use crate::theme::{AnsiColors, SpecialColors, Theme};
+use crate::yaml_parser::YamlParser;
+use std::collections::BTreeMap;
+
+const PALETTE_KEY: &str = "palette";
+
+/// Whether the contents look like a base16 or base24 scheme.
+pub fn is_scheme(contents: &str) -> bool {
+    YamlParser::parse::<Value>(contents)
+        .map(|value| slots(&value).get("base00").is_some())
+        .unwrap_or_default()
+}
+
+/// Imports both the classic layout with the slots at the top level and the newer one which nests
+/// them under `palette`.
+pub fn import(contents: &str) -> Result<Theme, ImportError> {
+    let value: Value = YamlParser::parse(contents)?;
+
+    let base = slots(&value)
+        .as_mapping()
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, value)| Some((key.as_str()?, value)))
+        .filter(|(key, _)| is_slot(key))
+        .map(|(key, value)| {
+            let key = normalize_slot(key);
+            // Unquoted slots made of digits only, like `111111`, are read as integers.
+            let hex = match (value.as_str(), value.as_u64()) {
+                (Some(hex), _) => format!("#{}", hex.trim_start_matches('#')),
+                (None, Some(number)) => format!("#{number:06}"),
+                (None, None) => {
+                    return Err(ImportError::InvalidColor(
+                        key,
+                        "expected a hex string".to_string(),
+                    ))
+                }
+            };
+            let color = Color::from_hex(&hex)
+                .map_err(|err| ImportError::InvalidColor(key.clone(), err.to_string()))?;
+
+            Ok((key, color))
+        })
+        .collect::<Result<BTreeMap<String, Color>, ImportError>>()?;
+
+    let slot = |name: &str| {
+        base.get(name)
+            .copied()
+            .ok_or(ImportError::MissingColor(name.to_string()))
+    };
+    // base24 schemes have dedicated bright colors, base16 ones reuse the normal colors.
+    let bright_slot = |name: &str, fallback: &str| slot(name).or_else(|_| slot(fallback));
+
+    let special = SpecialColors {
+        background: slot("base00")?,
+        foreground: slot("base05")?,
+        cursor: slot("base05")?,
+    };
+    let normal = AnsiColors {
+        black: slot("base00")?,
+        red: slot("base08")?,
+        green: slot("base0B")?,
+        yellow: slot("base0A")?,
+        blue: slot("base0D")?,
+        magenta: slot("base0E")?,
+        cyan: slot("base0C")?,
+        white: slot("base05")?,
+    };
+    let bright = AnsiColors {
+        black: slot("base03")?,
+        red: bright_slot("base12", "base08")?,
+        green: bright_slot("base14", "base0B")?,
+        yellow: bright_slot("base13", "base0A")?,
+        blue: bright_slot("base16", "base0D")?,
+        magenta: bright_slot("base17", "base0E")?,
+        cyan: bright_slot("base15", "base0C")?,
+        white: slot("base07")?,
+    };
+
+    Ok(Theme {
+        special,
+        normal,
+        bright,
+        base,
+    })
+}
+
+fn slots(value: &Value) -> &Value {
+    value.get(PALETTE_KEY).unwrap_or(value)
+}
+
+fn is_slot(key: &str) -> bool {
+    key.len() == 6
+        && key.starts_with("base")
+        && key[4..].chars().all(|digit| digit.is_ascii_hexdigit())
+}
+
+/// Slots are spelled `base0A` by the specification, but lowercase spellings are common too.
+fn normalize_slot(key: &str) -> String {
+    format!("base{}", key[4..].to_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classic() {
+        let theme = import(
+            "
+scheme: 'Ocean'
+author: 'Chris Kempson'
+base00: '2b303b'
+base01: 001100
+base02: '4f5b66'
+base03: '65737e'
+base04: 'a7adba'
+base05: 'c0c5ce'
+base06: 'dfe1e8'
+base07: 'eff1f5'
+base08: 'bf616a'
+base09: 'd08770'
+base0A: 'ebcb8b'
+base0B: 'a3be8c'
+base0C: '96b5b4'
+base0D: '8fa1b3'
+base0E: 'b48ead'
+base0F: 'ab7967'
+",
+        )
+        .unwrap();
+
+        assert_eq!(theme.special.background.to_hex(), "#2b303b");
+        assert_eq!(theme.special.foreground.to_hex(), "#c0c5ce");
+        assert_eq!(theme.normal.red.to_hex(), "#bf616a");
+        assert_eq!(theme.normal.blue.to_hex(), "#8fa1b3");
+        assert_eq!(theme.bright.black.to_hex(), "#65737e");
+        assert_eq!(theme.bright.red.to_hex(), "#bf616a");
+        assert_eq!(theme.bright.white.to_hex(), "#eff1f5");
+        assert_eq!(theme.base.len(), 16);
+        assert_eq!(theme.base.get("base0F").unwrap().to_hex(), "#ab7967");
+        assert_eq!(theme.base.get("base01").unwrap().to_hex(), "#001100");
+    }
+
+    #[test]
+    fn base24() {
+        let slots = (0..24)
+            .map(|slot| format!("  base{slot:02X}: '#{:02x}0000'", slot * 10))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let theme = import(&format!("system: base24\npalette:\n{slots}")).unwrap();
+
+        assert_eq!(theme.base.len(), 24);
+        assert_eq!(theme.normal.red.to_hex(), "#500000");
+        assert_eq!(theme.bright.red.to_hex(), "#b40000");
+        assert_eq!(theme.bright.magenta.to_hex(), "#e60000");
+
+        assert!(matches!(
+            import("base00: '000000'"),
+            Err(ImportError::MissingColor(slot)) if slot == "base05"
+        ));
+        assert!(matches!(
+            import("base00: [0, 0, 0]"),
+            Err(ImportError::InvalidColor(slot, err)) if slot == "base00" && err == "expected a hex string"
+        ));
+        assert!(matches!(
+            import("base00: 'nothex'"),
+            Err(ImportError::InvalidColor(slot, _)) if slot == "base00"
+        ));
+    }
+}
@@ -0,0 +1,108 @@
+use super::{Colors, ImportError};
+use crate::color::Color;
+use crate::theme::Theme;
+use plist::{Dictionary, Value};
+use std::io::Cursor;
+
+/// Imports an iTerm2 `.itermcolors` property list.
+pub fn import(contents: &str) -> Result<Theme, ImportError> {
+    let plist = Value::from_reader(Cursor::new(contents.as_bytes()))
+        .map_err(|err| ImportError::Syntax(err.to_string()))?;
+    let scheme = plist
+        .as_dictionary()
+        .ok_or(ImportError::Syntax("expected a dictionary".to_string()))?;
+
+    let special = [
+        ("background", "Background Color".to_string()),
+        ("foreground", "Foreground Color".to_string()),
+        ("cursor", "Cursor Color".to_string()),
+    ];
+    let ansi = (0..16).map(|index| (format!("color{index}"), format!("Ansi {index} Color")));
+
+    let mut colors = Colors::default();
+    for (key, name) in special
+        .into_iter()
+        .map(|(key, name)| (key.to_string(), name))
+        .chain(ansi)
+    {
+        if let Some(color) = scheme.get(&name).and_then(Value::as_dictionary) {
+            let color = component_color(color).ok_or(ImportError::InvalidColor(
+                name.clone(),
+                "missing components".to_string(),
+            ))?;
+            colors.insert(&key, color);
+        }
+    }
+
+    colors.into_theme()
+}
+
+/// iTerm2 stores each channel as a real number between 0 and 1.
+fn component_color(color: &Dictionary) -> Option<Color> {
+    let channel = |name: &str| {
+        let value = color.get(&format!("{name} Component"))?.as_real()?;
+        Some((value.clamp(0.0, 1.0) * 255.0).round() as u8)
+    };
+
+    Some(Color::new(
+        channel("Red")?,
+        channel("Green")?,
+        channel("Blue")?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(name: &str, red: f32, green: f32, blue: f32) -> String {
+        format!(
+            "<key>{name}</key>
+<dict>
+  <key>Color Space</key><string>sRGB</string>
+  <key>Red Component</key><real>{red}</real>
+  <key>Green Component</key><real>{green}</real>
+  <key>Blue Component</key><real>{blue}</real>
+</dict>"
+        )
+    }
+
+    #[test]
+    fn import() {
+        let ansi = (0..16)
+            .map(|index| {
+                color(
+                    &format!("Ansi {index} Color"),
+                    index as f32 / 15.0,
+                    0.0,
+                    0.0,
+                )
+            })
+            .collect::<String>();
+        let theme = super::import(&format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+{ansi}
+{}
+{}
+</dict>
+</plist>"#,
+            color("Background Color", 0.0, 0.0, 0.0),
+            color("Foreground Color", 1.0, 1.0, 1.0),
+        ))
+        .unwrap();
+
+        assert_eq!(theme.special.background.to_hex(), "#000000");
+        assert_eq!(theme.special.foreground.to_hex(), "#ffffff");
+        assert_eq!(theme.special.cursor.to_hex(), "#ffffff");
+        assert_eq!(theme.normal.black.to_hex(), "#000000");
+        assert_eq!(theme.bright.white.to_hex(), "#ff0000");
+
+        assert!(matches!(
+            super::import("not a plist"),
+            Err(ImportError::Syntax(_))
+        ));
+    }
+}
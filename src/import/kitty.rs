@@ -0,0 +1,54 @@
+use super::{is_color_key, Colors, ImportError};
+use crate::theme::Theme;
+
+/// Imports the color options of a Kitty configuration, everything else is ignored.
+pub fn import(contents: &str) -> Result<Theme, ImportError> {
+    let mut colors = Colors::default();
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once(char::is_whitespace) else {
+            continue;
+        };
+        // A cursor of `none` is drawn in reverse video, which leaves it to fall back to the
+        // foreground.
+        if is_color_key(key) && !(key == "cursor" && value.trim() == "none") {
+            colors.set(key, value)?;
+        }
+    }
+
+    colors.into_theme()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn import() {
+        let colors = (0..16)
+            .map(|index| format!("color{index:<3} #{:02x}0000", index * 16))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let config = format!(
+            "
+# vim:ft=kitty
+background            #282a36
+foreground            #f8f8f2
+selection_background  #44475a
+url_color             #ffb86c
+{colors}
+"
+        );
+        let theme = super::import(&config).unwrap();
+
+        assert_eq!(theme.special.background.to_hex(), "#282a36");
+        assert_eq!(theme.special.cursor.to_hex(), "#f8f8f2");
+        assert_eq!(theme.normal.red.to_hex(), "#100000");
+        assert_eq!(theme.bright.white.to_hex(), "#f00000");
+
+        let theme = super::import(&format!("{config}cursor none\n")).unwrap();
+        assert_eq!(theme.special.cursor.to_hex(), "#f8f8f2");
+    }
+}
@@ -0,0 +1,91 @@
+use super::{Colors, ImportError};
+use crate::theme::Theme;
+use serde_json::Value;
+
+const ANSI_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "purple", "cyan", "white",
+];
+
+/// Imports a Windows Terminal color scheme, either on its own or as the first entry of the
+/// `schemes` of a settings file.
+pub fn import(contents: &str) -> Result<Theme, ImportError> {
+    let settings: Value =
+        serde_json::from_str(contents).map_err(|err| ImportError::Syntax(err.to_string()))?;
+    let scheme = match settings.get("schemes") {
+        Some(Value::Array(schemes)) => schemes
+            .first()
+            .ok_or(ImportError::Syntax("no color schemes defined".to_string()))?,
+        _ => &settings,
+    };
+    let get = |name: &str| scheme.get(name).and_then(Value::as_str);
+
+    let mut colors = Colors::default();
+    for (key, name) in [
+        ("background", "background"),
+        ("foreground", "foreground"),
+        ("cursor", "cursorColor"),
+    ] {
+        if let Some(value) = get(name) {
+            colors.set(key, value)?;
+        }
+    }
+    for (index, name) in ANSI_NAMES.iter().enumerate() {
+        if let Some(value) = get(name) {
+            colors.set(&format!("color{index}"), value)?;
+        }
+
+        let bright = format!("bright{}{}", name[..1].to_uppercase(), &name[1..]);
+        if let Some(value) = get(&bright) {
+            colors.set(&format!("color{}", index + 8), value)?;
+        }
+    }
+
+    colors.into_theme()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import() {
+        let theme = super::import(
+            r##"{
+    "schemes": [{
+        "name": "Campbell",
+        "background": "#0C0C0C",
+        "foreground": "#CCCCCC",
+        "cursorColor": "#FFFFFF",
+        "black": "#0C0C0C",
+        "red": "#C50F1F",
+        "green": "#13A10E",
+        "yellow": "#C19C00",
+        "blue": "#0037DA",
+        "purple": "#881798",
+        "cyan": "#3A96DD",
+        "white": "#CCCCCC",
+        "brightBlack": "#767676",
+        "brightRed": "#E74856",
+        "brightGreen": "#16C60C",
+        "brightYellow": "#F9F1A5",
+        "brightBlue": "#3B78FF",
+        "brightPurple": "#B4009E",
+        "brightCyan": "#61D6D6",
+        "brightWhite": "#F2F2F2"
+    }]
+}"##,
+        )
+        .unwrap();
+
+        assert_eq!(theme.special.background.to_hex(), "#0c0c0c");
+        assert_eq!(theme.special.cursor.to_hex(), "#ffffff");
+        assert_eq!(theme.normal.magenta.to_hex(), "#881798");
+        assert_eq!(theme.bright.magenta.to_hex(), "#b4009e");
+        assert_eq!(theme.bright.white.to_hex(), "#f2f2f2");
+
+        assert!(matches!(
+            super::import(r#"{"schemes": []}"#),
+            Err(ImportError::Syntax(_))
+        ));
+    }
+}
@@ -0,0 +1,72 @@
+use super::{is_color_key, Colors, ImportError};
+use crate::theme::Theme;
+use std::collections::HashMap;
+
+/// Imports the color resources of an Xresources file. Resources may be scoped to any class, like
+/// `*.color0` or `URxvt*background`, and may refer to values from `#define` directives.
+pub fn import(contents: &str) -> Result<Theme, ImportError> {
+    let mut defines = HashMap::new();
+    let mut colors = Colors::default();
+
+    for line in contents.lines().map(str::trim) {
+        if let Some(define) = line.strip_prefix("#define") {
+            let mut parts = define.split_whitespace();
+            if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                defines.insert(name, value);
+            }
+            continue;
+        }
+        if line.is_empty() || line.starts_with('!') || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((resource, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = resource
+            .rsplit(['*', '.'])
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .replace("cursorColor", "cursor");
+        let value = value.trim();
+        let value = defines.get(value).copied().unwrap_or(value);
+
+        if is_color_key(&key) {
+            colors.set(&key, value)?;
+        }
+    }
+
+    colors.into_theme()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn import() {
+        let colors = (0..16)
+            .map(|index| format!("*.color{index}: #0000{:02x}", index * 16))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let theme = super::import(&format!(
+            "
+! Solarized
+#define S_base03 #002b36
+#define S_base0  #839496
+
+*background: S_base03
+URxvt*foreground: S_base0
+*.cursorColor: #93a1a1
+*.font: monospace
+{colors}
+"
+        ))
+        .unwrap();
+
+        assert_eq!(theme.special.background.to_hex(), "#002b36");
+        assert_eq!(theme.special.foreground.to_hex(), "#839496");
+        assert_eq!(theme.special.cursor.to_hex(), "#93a1a1");
+        assert_eq!(theme.normal.blue.to_hex(), "#000040");
+        assert_eq!(theme.bright.black.to_hex(), "#000080");
+    }
+}
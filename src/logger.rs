@@ -1,5 +1,5 @@
-pub use log::error;
 use log::LevelFilter;
+pub use log::{error, warn};
 use std::io::Write;
 
 pub struct Logger;
@@ -1,26 +1,47 @@
+mod backup;
 mod cli;
 mod color;
 mod config;
+mod diff;
 mod directories;
+mod export;
+mod glob;
 mod hook;
+mod import;
 mod logger;
 mod os;
+mod preview;
 mod renderer;
+mod schedule;
+mod sequences;
+mod state;
 mod template;
 mod theme;
+mod wallpaper;
+mod watcher;
 mod yaml_parser;
 
-use cli::Args;
+use backup::{BackupError, Generation};
+use cli::{ApplyArgs, Args, Command, ExportArgs, ImportArgs, LintArgs, ThemeArgs};
 use config::{Config, ConfigError};
 use directories::Directories;
+use export::{ExportError, ExportFormat};
 use hook::Hook;
-use logger::{error, Logger};
-use os::{Path, ReadDirError};
-use renderer::{context, Renderer, Value};
-use std::{collections::HashMap, process::ExitCode};
-use template::Template;
+use import::{Format, ImportError};
+use logger::{error, warn, Logger};
+use os::{Path, PathBuf, ReadDirError, ReadError, WriteError};
+use renderer::{context, Context, Renderer, Serialize, Value};
+use schedule::{LocalTime, ScheduleError};
+use sequences::Sequences;
+use state::{State, StateError};
+use std::{collections::HashMap, process::ExitCode, time::Duration};
+use template::{Partial, RenderStatus, Template};
 use theme::{Theme, ThemeError};
 use thiserror::Error;
+use watcher::Watcher;
+use yaml_parser::ParseError;
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
 
 #[derive(Debug, Error)]
 enum ListThemesError {
@@ -28,6 +49,27 @@ enum ListThemesError {
     ReadDir(#[from] ReadDirError),
 }
 
+#[derive(Debug, Error)]
+enum ImportThemeError {
+    #[error("unknown format, specify one with --format")]
+    UnknownFormat,
+
+    #[error("theme '{0}' already exists, overwrite it with --force")]
+    AlreadyExists(String),
+
+    #[error("read failed -> {0}")]
+    Read(#[from] ReadError),
+
+    #[error("conversion failed -> {0}")]
+    Import(#[from] ImportError),
+
+    #[error("serialization failed -> {0}")]
+    Serialize(#[from] ParseError),
+
+    #[error("write failed -> {0}")]
+    Write(#[from] WriteError),
+}
+
 #[derive(Debug, Error)]
 enum AppError {
     #[error("could not read config -> {0}")]
@@ -39,8 +81,35 @@ enum AppError {
     #[error("no theme specified")]
     NoThemeSpecified,
 
+    #[error("no schedule configured")]
+    NoSchedule,
+
+    #[error("could not pick a scheduled theme -> {0}")]
+    Schedule(#[from] ScheduleError),
+
     #[error("invalid theme -> {0}")]
     Theme(#[from] ThemeError),
+
+    #[error("could not load state -> {0}")]
+    State(#[from] StateError),
+
+    #[error("could not export theme -> {0}")]
+    Export(#[from] ExportError),
+
+    #[error("could not import theme -> {0}")]
+    Import(#[from] ImportThemeError),
+
+    #[error("could not roll back -> {0}")]
+    Rollback(#[from] BackupError),
+
+    #[error("{0} template(s) or partial(s) could not be parsed")]
+    InvalidTemplates(usize),
+
+    #[error("validation failed with {0} error(s)")]
+    Invalid(usize),
+
+    #[error("{0} color(s) below the minimum contrast")]
+    LowContrast(usize),
 }
 
 fn main() -> ExitCode {
@@ -55,88 +124,657 @@ fn main() -> ExitCode {
     }
 }
 
-// TODO: Think of saving colorscheme to sequences for cross-terminal support
 fn run() -> Result<(), AppError> {
     let cli_args = Args::parse();
-    let config_dir = &cli_args.config_dir;
+    let config_dir = cli_args.config_dir.clone();
     let dirs = Directories::new(&config_dir);
 
-    if cli_args.list_themes {
-        return Ok(list_themes(&dirs.theme_dir)?);
+    match cli_args.into_command() {
+        Command::Apply(args) => apply(args, &config_dir, &dirs),
+        Command::List => Ok(list_themes(&dirs.theme_dir)?),
+        Command::Preview(args) => {
+            let default_theme = default_theme(&args, &config_dir)?;
+            let (_, theme) = load_theme(args, default_theme, &dirs)?;
+            println!("{}", preview::preview(&theme));
+
+            Ok(())
+        }
+        Command::Validate(args) => validate(args, &config_dir, &dirs),
+        Command::Lint(args) => lint(args, &config_dir, &dirs),
+        Command::Current => {
+            let state = State::load(&dirs.cache_dir)?;
+            println!("{}", state.theme);
+
+            Ok(())
+        }
+        Command::Export(args) => export(args, &config_dir, &dirs),
+        Command::Import(args) => Ok(import_theme(args, &dirs)?),
+        Command::Rollback => {
+            let targets = Generation::latest(&dirs.cache_dir)?.restore()?;
+            targets
+                .iter()
+                .for_each(|target| println!("{}", target.display()));
+
+            Ok(())
+        }
+    }
+}
+
+fn apply(args: ApplyArgs, config_dir: &Path, dirs: &Directories) -> Result<(), AppError> {
+    let config = Config::new(config_dir)?;
+    let state = load_state(&args, &config, dirs)?;
+
+    let templates = config.templates.unwrap_or_default();
+    let templates = load_templates(&templates, &state.colors, &state.variables, dirs);
+
+    let hooks = args
+        .hooks
+        .clone()
+        .unwrap_or(config.hooks.unwrap_or_default());
+    let hooks = load_hooks(&hooks, dirs);
+
+    let send_sequences = args.sequences || config.sequences.unwrap_or_default();
+
+    // Nothing is written unless every template parses.
+    let mut renderer = Renderer::new(template_context(&state.colors, &state.variables));
+    compile_templates(&load_partials(dirs), &templates, &mut renderer)?;
+
+    if args.dry_run {
+        preview_templates(&templates, &renderer);
+        if send_sequences {
+            println!("would send sequences");
+        }
+        hooks
+            .iter()
+            .for_each(|hook| println!("would execute hook '{}'", hook.name));
+
+        return Ok(());
+    }
+
+    if let Some(keep) = config.backups.filter(|keep| *keep > 0) {
+        back_up_targets(&templates, &renderer, keep, &dirs.cache_dir);
+    }
+    state
+        .save(&dirs.cache_dir)
+        .unwrap_or_else(|err| error!("could not save state -> {err}"));
+    let statuses = render_templates(&templates, &renderer);
+    if send_sequences {
+        apply_sequences(&state.colors, &dirs.cache_dir);
+    }
+    execute_hooks(&hooks, &state.variables, &statuses);
+
+    if args.watch {
+        watch(&args, state, renderer, config_dir, dirs);
     }
 
-    let config = Config::new(&config_dir)?;
-    let theme_name = cli_args
-        .theme
-        .or(config.theme)
-        .ok_or(AppError::NoThemeSpecified)?;
-    let theme = Theme::new(&theme_name, &dirs.theme_dir)?;
+    Ok(())
+}
 
+/// Loads the theme and variables to apply.
+fn load_state(args: &ApplyArgs, config: &Config, dirs: &Directories) -> Result<State, AppError> {
     let mut variables = HashMap::new();
-    if let Some(config_vars) = config.variables {
-        variables.extend(config_vars.into_iter());
+    let (theme_name, theme) = if args.restore {
+        let state = State::load(&dirs.cache_dir)?;
+        variables.extend(state.variables);
+
+        (state.theme, state.colors)
+    } else {
+        if let Some(config_vars) = &config.variables {
+            variables.extend(config_vars.clone());
+        }
+        let default_theme = match args.auto {
+            true => {
+                let schedule = config.schedule.as_ref().ok_or(AppError::NoSchedule)?;
+                Some(schedule.theme_at(LocalTime::now())?.to_string())
+            }
+            false => config.theme.clone(),
+        };
+
+        load_theme(args.theme.clone(), default_theme, dirs)?
+    };
+    if let Some(cli_vars) = &args.variables {
+        variables.extend(cli_vars.clone());
     }
-    if let Some(cli_vars) = cli_args.variables {
-        variables.extend(cli_vars.into_iter());
+
+    Ok(State::new(&theme_name, theme, variables))
+}
+
+/// Re-renders the templates whenever they, the configuration or the theme change. Changes to the
+/// configuration or the theme re-render every template, otherwise only the changed ones are. The
+/// renderer keeps the compiled templates, so only new or changed ones are parsed again.
+fn watch(
+    args: &ApplyArgs,
+    mut state: State,
+    mut renderer: Renderer<Context>,
+    config_dir: &Path,
+    dirs: &Directories,
+) -> ! {
+    let config_file = Config::file(config_dir);
+    let theme_file = |state: &State| match &args.theme.image {
+        Some(image) => Some(image.clone()),
+        None if args.restore => None,
+        None => Theme::file(&state.theme, &dirs.theme_dir),
+    };
+    let watched = |state: &State| {
+        let mut paths = vec![config_file.clone(), dirs.template_dir.clone()];
+        paths.extend(theme_file(state));
+
+        paths
+    };
+    let mut watcher = Watcher::new(watched(&state));
+    // Set when the theme could not be loaded, so that the next change retries as if the
+    // configuration had changed.
+    let mut failed = false;
+
+    loop {
+        let changes = watcher.wait(WATCH_INTERVAL);
+        let config = match Config::new(config_dir) {
+            Ok(config) => config,
+            Err(err) => {
+                error!("could not read config -> {err}");
+                continue;
+            }
+        };
+        let config_changed = failed || changes.contains(&config_file);
+        let theme_file = theme_file(&state);
+        let theme_changed =
+            config_changed || changes.iter().any(|file| Some(file) == theme_file.as_ref());
+        let partials_changed = changes
+            .iter()
+            .any(|file| file.starts_with(&dirs.partial_dir));
+        if theme_changed {
+            match load_state(args, &config, dirs) {
+                Ok(new_state) => {
+                    state = new_state;
+                    state
+                        .save(&dirs.cache_dir)
+                        .unwrap_or_else(|err| error!("could not save state -> {err}"));
+                }
+                Err(err) => {
+                    error!("{err}");
+                    // The theme may be missing until it is created, under a name which is not
+                    // known to resolve to any file yet.
+                    failed = true;
+                    let mut paths = watched(&state);
+                    paths.push(dirs.theme_dir.clone());
+                    watcher = Watcher::new(paths);
+                    continue;
+                }
+            }
+            failed = false;
+            // The theme file itself changes when the configuration names another theme.
+            watcher = Watcher::new(watched(&state));
+        }
+        // Which templates apply depends on the state.
+        let templates = config.templates.as_deref().unwrap_or_default();
+        let templates = load_templates(templates, &state.colors, &state.variables, dirs);
+        let context = template_context(&state.colors, &state.variables);
+
+        // Any template may include a partial and the configuration may add templates, so both
+        // start over with a new renderer, which only replaces the current one if it compiles.
+        let templates = if config_changed || partials_changed {
+            let mut new_renderer = Renderer::new(context);
+            if let Err(err) = compile_templates(&load_partials(dirs), &templates, &mut new_renderer)
+            {
+                error!("{err}");
+                continue;
+            }
+            renderer = new_renderer;
+
+            templates
+        } else if theme_changed {
+            if let Err(err) = switch_theme(&templates, &changes, context, &mut renderer) {
+                error!("{err}");
+                continue;
+            }
+
+            templates
+        } else {
+            let templates = templates
+                .into_iter()
+                .filter(|template| changes.iter().any(|file| file == template.source()))
+                .collect::<Vec<Template>>();
+            if let Err(err) = compile_templates(&[], &templates, &mut renderer) {
+                error!("{err}");
+                continue;
+            }
+
+            templates
+        };
+        render_templates(&templates, &renderer);
     }
+}
+
+fn validate(args: ThemeArgs, config_dir: &Path, dirs: &Directories) -> Result<(), AppError> {
+    let config = Config::new(config_dir)?;
+    let (_, theme) = load_theme(args, config.theme, dirs)?;
+    let variables = config.variables.unwrap_or_default();
 
     let templates = config.templates.unwrap_or_default();
-    let templates = templates
-        .iter()
-        .map(|template| Template::new(&template.source, &template.target, &dirs.template_dir))
-        .collect::<Vec<Template>>();
+    let templates = load_templates(&templates, &theme, &variables, dirs);
+    let hooks = config.hooks.unwrap_or_default();
+    let hooks = load_hooks(&hooks, dirs);
+
+    let mut renderer = Renderer::new(template_context(&theme, &variables));
 
-    let hooks = cli_args.hooks.unwrap_or(config.hooks.unwrap_or_default());
-    let hooks = hooks
+    let partial_errors = load_partials(dirs)
         .iter()
-        .map(|hook| Hook::new(hook, &dirs.hook_dir))
-        .collect::<Vec<Hook>>();
+        .filter_map(|partial| {
+            partial
+                .compile(&mut renderer)
+                .err()
+                .map(|err| (partial, err))
+        })
+        .map(|(partial, err)| error!("invalid partial '{}' -> {err}", partial.name))
+        .count();
+    let template_errors = templates
+        .iter()
+        .filter_map(|template| {
+            template
+                .compile(&mut renderer)
+                .and_then(|_| template.render_to_bytes(&renderer))
+                .err()
+                .map(|err| (template, err))
+        })
+        .map(|(template, err)| error!("invalid template '{}' -> {err}", template.name))
+        .count();
+    let hook_errors = hooks
+        .iter()
+        .filter(|hook| !hook.exists())
+        .map(|hook| error!("invalid hook '{}': does not exist", hook.name))
+        .count();
+
+    match partial_errors + template_errors + hook_errors {
+        0 => Ok(()),
+        errors => Err(AppError::Invalid(errors)),
+    }
+}
+
+fn lint(args: LintArgs, config_dir: &Path, dirs: &Directories) -> Result<(), AppError> {
+    let default_theme = default_theme(&args.theme, config_dir)?;
+    let (theme_name, mut theme) = load_theme(args.theme, default_theme, dirs)?;
+    let background = theme.special.background;
+
+    let mut failures = 0;
+    for (name, color) in theme.text_colors_mut() {
+        let contrast = color.contrast(background);
+        if contrast >= args.min_contrast {
+            continue;
+        }
+
+        failures += 1;
+        if args.fix {
+            let fixed = color.readable(background, args.min_contrast);
+            warn!(
+                "adjusted '{name}' from {color} to {fixed}: contrast {contrast:.2} -> {:.2}",
+                fixed.contrast(background)
+            );
+            *color = fixed;
+        } else {
+            error!(
+                "'{name}' {color} has a contrast of {contrast:.2} against the background, below {:.2}",
+                args.min_contrast
+            );
+        }
+    }
+
+    if args.fix {
+        print!(
+            "{}",
+            export::export(ExportFormat::Yaml, &theme_name, &theme)?
+        );
+        return Ok(());
+    }
+
+    match failures {
+        0 => Ok(()),
+        failures => Err(AppError::LowContrast(failures)),
+    }
+}
 
-    render_templates(&templates, &theme, &variables);
-    execute_hooks(&hooks, &variables);
+fn export(args: ExportArgs, config_dir: &Path, dirs: &Directories) -> Result<(), AppError> {
+    let default_theme = default_theme(&args.theme, config_dir)?;
+    let (theme_name, theme) = load_theme(args.theme, default_theme, dirs)?;
+    let contents = export::export(args.format, &theme_name, &theme)?;
+
+    match args.output {
+        Some(output) => os::write_to_file(output, &contents).map_err(ExportError::from)?,
+        None => print!("{contents}"),
+    }
 
     Ok(())
 }
 
-fn list_themes(theme_dir: &Path) -> Result<(), ListThemesError> {
-    let files = os::read_dir(theme_dir)?;
+fn import_theme(args: ImportArgs, dirs: &Directories) -> Result<(), ImportThemeError> {
+    let file_name = args
+        .file
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let guessed = Format::from_file_name(file_name);
+    let format = args
+        .format
+        .or(guessed.map(|(_, format)| format))
+        .ok_or(ImportThemeError::UnknownFormat)?;
+    let name = args
+        .name
+        .or(guessed.map(|(name, _)| name.to_string()))
+        .unwrap_or(file_name.to_string());
+
+    // Any file of the theme counts, as the imported one would take precedence over it.
+    let target = dirs.theme_dir.join(format!("{name}.yaml"));
+    let exists = Theme::list(&dirs.theme_dir).is_ok_and(|themes| themes.contains(&name));
+    if exists && !args.force {
+        return Err(ImportThemeError::AlreadyExists(name));
+    }
+
+    let contents = os::read_file(&args.file)?;
+    let theme = import::import(format.detect(&contents), &contents)?;
+    os::create_dir(&dirs.theme_dir)?;
+    os::write_to_file(target, &yaml_parser::to_string(&theme)?)?;
+
+    Ok(())
+}
+
+/// Reads the configured theme, but only when none was given on the command line, so that themes
+/// can be inspected without a configuration.
+fn default_theme(args: &ThemeArgs, config_dir: &Path) -> Result<Option<String>, AppError> {
+    if args.theme.is_some() || args.image.is_some() {
+        return Ok(None);
+    }
+
+    Ok(Config::new(config_dir)?.theme)
+}
+
+fn load_theme(
+    args: ThemeArgs,
+    default_theme: Option<String>,
+    dirs: &Directories,
+) -> Result<(String, Theme), AppError> {
+    let (theme_name, theme) = match args.image {
+        Some(image) => (image.display().to_string(), Theme::from_image(&image)?),
+        None => {
+            let theme_name = args
+                .theme
+                .or(default_theme)
+                .ok_or(AppError::NoThemeSpecified)?;
+            let theme = Theme::new(&theme_name, &dirs.theme_dir)?;
+
+            (theme_name, theme)
+        }
+    };
 
-    let mut themes = files
+    match args.variant {
+        Some(variant) => Ok((theme_name, theme.variant(variant))),
+        None => Ok((theme_name, theme)),
+    }
+}
+
+/// The templates of every configured source whose condition holds, leaving out the partials that
+/// directory and glob sources may match. Templates with variables of their own are rendered with
+/// those merged over the global ones.
+fn load_templates(
+    templates: &[config::Template],
+    theme: &Theme,
+    variables: &HashMap<String, Value>,
+    dirs: &Directories,
+) -> Vec<Template> {
+    templates
         .iter()
-        .filter(|file| file.extension().unwrap_or_default() == "yaml")
-        .map(|file| {
-            file.file_stem()
-                .unwrap_or_default()
-                .to_str()
-                .unwrap_or_default()
+        .filter(|template| {
+            template
+                .when
+                .as_ref()
+                .is_none_or(|when| when.holds(theme.is_dark(), variables))
         })
-        .collect::<Vec<&str>>();
-    themes.sort();
+        .flat_map(|template| {
+            let mut expanded = Template::expand(
+                &template.source,
+                &template.target,
+                &dirs.template_dir,
+                template.write_options(),
+                &template.filter(),
+            )
+            .unwrap_or_else(|err| {
+                error!(
+                    "could not read template source '{}' -> {err}",
+                    template.source
+                );
+
+                vec![]
+            });
+            if !template.variables.is_empty() {
+                let mut variables = variables.clone();
+                variables.extend(template.variables.clone());
+                let context = template_context(theme, &variables);
+                expanded
+                    .iter_mut()
+                    .for_each(|template| template.set_context(context.clone()));
+            }
+
+            expanded
+        })
+        .filter(|template| !template.source().starts_with(&dirs.partial_dir))
+        .collect()
+}
+
+fn load_partials(dirs: &Directories) -> Vec<Partial> {
+    Partial::list(&dirs.partial_dir).unwrap_or_else(|err| {
+        error!("could not read partials -> {err}");
+
+        vec![]
+    })
+}
+
+fn load_hooks<'a>(hooks: &'a [String], dirs: &Directories) -> Vec<Hook<'a>> {
+    hooks
+        .iter()
+        .map(|hook| Hook::new(hook, &dirs.hook_dir))
+        .collect()
+}
+
+fn list_themes(theme_dir: &Path) -> Result<(), ListThemesError> {
+    let themes = Theme::list(theme_dir)?;
 
     themes.into_iter().for_each(|theme| println!("{theme}"));
 
     Ok(())
 }
 
-fn render_templates(templates: &[Template], theme: &Theme, variables: &HashMap<String, Value>) {
-    let context = context!({
+fn template_context(theme: &Theme, variables: &HashMap<String, Value>) -> Context {
+    context!({
         "variables": variables,
-        "colors": theme,
-    });
-    let renderer = Renderer::new(&context);
+        "colors": theme.context(),
+    })
+}
+
+/// Compiles the partials and then the templates, reporting every one that does not parse rather
+/// than only the first.
+fn compile_templates<'a, T: Serialize>(
+    partials: &[Partial],
+    templates: impl IntoIterator<Item = &'a Template>,
+    renderer: &mut Renderer<T>,
+) -> Result<(), AppError> {
+    let partial_errors = partials
+        .iter()
+        .filter_map(|partial| partial.compile(renderer).err().map(|err| (partial, err)))
+        .map(|(partial, err)| error!("invalid partial '{}' -> {err}", partial.name))
+        .count();
+    let template_errors = templates
+        .into_iter()
+        .filter_map(|template| template.compile(renderer).err().map(|err| (template, err)))
+        .map(|(template, err)| error!("invalid template '{}' -> {err}", template.name))
+        .count();
+
+    match partial_errors + template_errors {
+        0 => Ok(()),
+        errors => Err(AppError::InvalidTemplates(errors)),
+    }
+}
 
+/// Renders the templates against another theme from now on. Its polarity, and with it the `when`
+/// conditions, may include templates that have not been compiled yet, and the sources among the
+/// `changes` are compiled again since they may have been edited along with the theme.
+fn switch_theme(
+    templates: &[Template],
+    changes: &[PathBuf],
+    context: Context,
+    renderer: &mut Renderer<Context>,
+) -> Result<(), AppError> {
+    renderer.set_context(context);
+    let outdated = templates
+        .iter()
+        .filter(|template| {
+            !renderer.is_registered(&template.name)
+                || changes.iter().any(|file| file == template.source())
+        })
+        .collect::<Vec<&Template>>();
+
+    compile_templates(&[], outdated, renderer)
+}
+
+/// Renders every compiled template concurrently and prints how many targets were written, were
+/// already up to date or failed.
+fn render_templates<'a, T: Serialize + Sync>(
+    templates: &'a [Template],
+    renderer: &Renderer<T>,
+) -> Vec<(&'a str, RenderStatus)> {
+    // Errors are reported once everything is rendered so that they come in the templates' order.
+    let statuses = templates
+        .iter()
+        .zip(template::render_all(templates, renderer))
+        .map(|(template, result)| {
+            let status = result.unwrap_or_else(|err| {
+                error!("could not render template '{}' -> {err}", template.name);
+
+                RenderStatus::Failed
+            });
+
+            (template.name.as_str(), status)
+        })
+        .collect::<Vec<(&str, RenderStatus)>>();
+
+    if !statuses.is_empty() {
+        let summary = [
+            RenderStatus::Written,
+            RenderStatus::Unchanged,
+            RenderStatus::Failed,
+        ]
+        .map(|status| {
+            let count = statuses.iter().filter(|(_, s)| *s == status).count();
+            format!("{count} {}", status.name())
+        });
+        println!("templates: {}", summary.join(", "));
+    }
+
+    statuses
+}
+
+/// Keeps the current contents of the targets that rendering will change, along with the state, so
+/// that they can be rolled back to. Nothing is kept when no target changes or the latest backups
+/// already hold the same contents, so that applying repeatedly does not push out older backups.
+fn back_up_targets<T: Serialize>(
+    templates: &[Template],
+    renderer: &Renderer<T>,
+    keep: usize,
+    cache_dir: &Path,
+) {
+    let mut generation = Generation::new(cache_dir);
+    let targets = templates
+        .iter()
+        .filter(|template| template.needs_write(renderer))
+        .map(|template| template.target());
+
+    for target in targets {
+        generation
+            .back_up(target)
+            .unwrap_or_else(|err| error!("could not back up '{}' -> {err}", target.display()));
+    }
+    match generation.is_in_latest(cache_dir) {
+        _ if generation.is_empty() => return,
+        Ok(true) => return,
+        Ok(false) => {}
+        Err(err) => error!("could not read the latest backups -> {err}"),
+    }
+    let state_file = State::file(cache_dir);
+    generation
+        .back_up(&state_file)
+        .unwrap_or_else(|err| error!("could not back up '{}' -> {err}", state_file.display()));
+    generation
+        .save()
+        .and_then(|_| Generation::prune(cache_dir, keep))
+        .unwrap_or_else(|err| error!("could not save backups -> {err}"));
+}
+
+/// Prints how rendering would change every target, without writing to them.
+fn preview_templates<T: Serialize>(templates: &[Template], renderer: &Renderer<T>) {
     templates.iter().for_each(|template| {
-        template
-            .render(&renderer)
-            .unwrap_or_else(|err| error!("could not render template '{}' -> {err}", template.name));
+        let rendered = match template.render_to_bytes(renderer) {
+            Ok(rendered) => rendered,
+            Err(err) => {
+                error!("could not render template '{}' -> {err}", template.name);
+                return;
+            }
+        };
+        let target = template.target().display().to_string();
+        let current = match os::read_bytes(template.target()) {
+            Ok(current) => current,
+            Err(ReadError::FileNotFound) => vec![],
+            Err(err) => {
+                error!("could not read target '{target}' -> {err}");
+                return;
+            }
+        };
+
+        // Like diff, only tell whether files that are not text differ.
+        match (
+            std::str::from_utf8(&current),
+            std::str::from_utf8(&rendered),
+        ) {
+            (Ok(current), Ok(rendered)) => {
+                print!("{}", diff::unified(current, rendered, &target, &target))
+            }
+            _ if current != rendered => println!("Binary file {target} differs"),
+            _ => {}
+        }
     });
 }
 
-fn execute_hooks(hooks: &[Hook], variables: &HashMap<String, Value>) {
-    let variables = variables
+fn apply_sequences(theme: &Theme, cache_dir: &Path) {
+    let sequences = Sequences::new(theme);
+
+    sequences
+        .save(cache_dir)
+        .unwrap_or_else(|err| error!("could not save sequences -> {err}"));
+    sequences
+        .send_all()
+        .map(|_| ())
+        .unwrap_or_else(|err| error!("could not send sequences -> {err}"));
+}
+
+/// Runs the hooks with the variables and, as `RSWAL_WRITTEN`, `RSWAL_UNCHANGED` and
+/// `RSWAL_FAILED`, the newline separated names of the templates in each render status.
+fn execute_hooks(
+    hooks: &[Hook],
+    variables: &HashMap<String, Value>,
+    statuses: &[(&str, RenderStatus)],
+) {
+    let mut variables = variables
         .iter()
-        .map(|(k, v)| (k.as_str(), yaml_parser::to_string(v).unwrap_or_default()))
-        .collect::<Vec<(&str, String)>>();
+        .map(|(k, v)| (k.to_string(), yaml_parser::to_string(v).unwrap_or_default()))
+        .collect::<Vec<(String, String)>>();
+    for status in [
+        RenderStatus::Written,
+        RenderStatus::Unchanged,
+        RenderStatus::Failed,
+    ] {
+        let names = statuses
+            .iter()
+            .filter(|(_, s)| *s == status)
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>();
+        variables.push((format!("rswal_{}", status.name()), names.join("\n")));
+    }
 
     hooks.iter().for_each(|hook| {
         let output = hook.execute(&variables).unwrap_or_else(|err| {
@@ -150,3 +788,102 @@ fn execute_hooks(hooks: &[Hook], variables: &HashMap<String, Value>) {
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color::Color;
+    use theme::Variant;
+    use yaml_parser::YamlParser;
+
+    #[test]
+    fn switch_polarity() {
+        let dir = tempfile::tempdir().unwrap();
+        let dirs = Directories::new(dir.path());
+        os::create_dir(&dirs.template_dir).unwrap();
+        for variant in ["dark", "light"] {
+            os::write_to_file(dirs.template_dir.join(variant), variant).unwrap();
+        }
+        let templates: Vec<config::Template> = YamlParser::parse(&format!(
+            "
+- {{source: dark, target: {0}/dark, when: {{variant: dark}}}}
+- {{source: light, target: {0}/light, when: {{variant: light}}}}
+",
+            dir.path().display()
+        ))
+        .unwrap();
+        let variables = HashMap::new();
+
+        let dark = Theme::from_palette(&[
+            Color::from_hex("#101010").unwrap(),
+            Color::from_hex("#e0e0e0").unwrap(),
+        ]);
+        let loaded = load_templates(&templates, &dark, &variables, &dirs);
+        let mut renderer = Renderer::new(template_context(&dark, &variables));
+        compile_templates(&[], &loaded, &mut renderer).unwrap();
+        assert_eq!(
+            render_templates(&loaded, &renderer),
+            [("dark", RenderStatus::Written)]
+        );
+
+        let light = dark.variant(Variant::Light);
+        let loaded = load_templates(&templates, &light, &variables, &dirs);
+        switch_theme(
+            &loaded,
+            &[],
+            template_context(&light, &variables),
+            &mut renderer,
+        )
+        .unwrap();
+        assert_eq!(
+            render_templates(&loaded, &renderer),
+            [("light", RenderStatus::Written)]
+        );
+    }
+
+    #[test]
+    fn watch_theme_and_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let dirs = Directories::new(dir.path());
+        let source = dirs.template_dir.join("colors");
+        let theme_file = dirs.theme_dir.join("theme.yaml");
+        os::create_dir(&dirs.template_dir).unwrap();
+        os::create_dir(&dirs.theme_dir).unwrap();
+        os::write_to_file(&source, "old {{colors.special.background}}").unwrap();
+        let theme = Theme::from_palette(&[Color::from_hex("#101010").unwrap()]);
+        os::write_to_file(&theme_file, yaml_parser::to_string(&theme).unwrap()).unwrap();
+        let templates: Vec<config::Template> = YamlParser::parse(&format!(
+            "[{{source: colors, target: {}/colors}}]",
+            dir.path().display()
+        ))
+        .unwrap();
+        let variables = HashMap::new();
+
+        let loaded = load_templates(&templates, &theme, &variables, &dirs);
+        let mut renderer = Renderer::new(template_context(&theme, &variables));
+        compile_templates(&[], &loaded, &mut renderer).unwrap();
+        let mut watcher = Watcher::new(vec![dirs.template_dir.clone(), theme_file.clone()]);
+
+        // Both change before the watcher polls again.
+        os::write_to_file(&source, "new {{colors.special.background}}").unwrap();
+        let theme = Theme::from_palette(&[Color::from_hex("#202020").unwrap()]);
+        os::write_to_file(&theme_file, yaml_parser::to_string(&theme).unwrap()).unwrap();
+        let changes = watcher.changes();
+        assert!(changes.contains(&source) && changes.contains(&theme_file));
+
+        let theme = Theme::new("theme", &dirs.theme_dir).unwrap();
+        let loaded = load_templates(&templates, &theme, &variables, &dirs);
+        switch_theme(
+            &loaded,
+            &changes,
+            template_context(&theme, &variables),
+            &mut renderer,
+        )
+        .unwrap();
+        render_templates(&loaded, &renderer);
+        assert_eq!(
+            os::read_file(dir.path().join("colors")).unwrap(),
+            format!("new {}", theme.special.background)
+        );
+    }
+}
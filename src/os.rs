@@ -1,8 +1,16 @@
-use std::fs;
-use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+use crate::yaml_parser::Deserialize;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Read, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
 pub use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
 use thiserror::Error;
 
+const BINARY_SNIFF_LEN: u64 = 8192;
+
 #[derive(Error, Debug)]
 pub enum ReadError {
     #[error("file not found")]
@@ -77,8 +85,135 @@ pub fn read_file<T: AsRef<Path>>(path: T) -> Result<String, ReadError> {
     Ok(fs::read_to_string(path)?)
 }
 
-pub fn write_to_file<T: AsRef<Path>>(path: T, contents: &str) -> Result<(), WriteError> {
-    Ok(fs::write(path, contents)?)
+pub fn read_bytes<T: AsRef<Path>>(path: T) -> Result<Vec<u8>, ReadError> {
+    Ok(fs::read(path)?)
+}
+
+/// Whether the file is binary rather than UTF-8 text, which it is taken to be if its first few KiB
+/// hold a NUL byte or are not valid UTF-8.
+pub fn is_binary<T: AsRef<Path>>(path: T) -> Result<bool, ReadError> {
+    let mut head = vec![];
+    File::open(path)?
+        .take(BINARY_SNIFF_LEN)
+        .read_to_end(&mut head)?;
+    // A character may be cut off at the end of the head, which is no reason to doubt the rest.
+    let is_utf8 = match std::str::from_utf8(&head) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    };
+
+    Ok(head.contains(&0) || !is_utf8)
+}
+
+/// What to do when a target is a symlink.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Symlinks {
+    /// Write to the file the symlink points to.
+    #[default]
+    Follow,
+    /// Replace the symlink with a regular file.
+    Replace,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WriteOptions {
+    /// Create the missing parent directories.
+    pub create_dirs: bool,
+    /// The permissions of the file, otherwise those of the file being replaced are kept.
+    pub mode: Option<u32>,
+    pub symlinks: Symlinks,
+}
+
+pub fn write_to_file<T: AsRef<Path>, C: AsRef<[u8]>>(
+    path: T,
+    contents: C,
+) -> Result<(), WriteError> {
+    write_with_options(path, contents, WriteOptions::default())
+}
+
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes to a temporary file next to the target and renames it over the target, so that the
+/// target is never left half written.
+pub fn write_with_options<T: AsRef<Path>, C: AsRef<[u8]>>(
+    path: T,
+    contents: C,
+    options: WriteOptions,
+) -> Result<(), WriteError> {
+    let path = match options.symlinks {
+        Symlinks::Follow => follow_symlinks(path.as_ref()),
+        Symlinks::Replace => path.as_ref().to_path_buf(),
+    };
+    if let (true, Some(parent)) = (options.create_dirs, path.parent()) {
+        create_dir(parent)?;
+    }
+    let Some(file_name) = path.file_name() else {
+        return Ok(fs::write(path, contents)?);
+    };
+    // Templates rendered concurrently may share a target, so every write gets its own temp file.
+    let temp_file = path.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        file_name.to_string_lossy(),
+        process::id(),
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let write = || {
+        // The temp file gets its permissions before anything is written to it, as the contents
+        // may be meant for the owner only. The mode given to open is reduced by the umask, so it
+        // is set again to be exact.
+        let mode = options.mode.or_else(|| mode(&path));
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(mode.unwrap_or(0o666))
+            .open(&temp_file)?;
+        if let Some(mode) = mode {
+            file.set_permissions(Permissions::from_mode(mode))?;
+        }
+        file.write_all(contents.as_ref())?;
+        file.sync_all()?;
+
+        fs::rename(&temp_file, &path)
+    };
+
+    write().map_err(|err| {
+        let _ = fs::remove_file(&temp_file);
+        err.into()
+    })
+}
+
+/// Resolves symlinks, including dangling ones, to the path they eventually point to.
+fn follow_symlinks(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+
+    // Give up on cycles the way the kernel does.
+    for _ in 0..40 {
+        match fs::read_link(&path) {
+            Ok(target) => {
+                path = match path.parent() {
+                    Some(parent) => parent.join(target),
+                    None => target,
+                }
+            }
+            Err(_) => break,
+        }
+    }
+
+    path
+}
+
+pub fn remove_file<T: AsRef<Path>>(path: T) -> Result<(), WriteError> {
+    Ok(fs::remove_file(path)?)
+}
+
+pub fn remove_dir<T: AsRef<Path>>(path: T) -> Result<(), WriteError> {
+    Ok(fs::remove_dir_all(path)?)
+}
+
+pub fn create_dir<T: AsRef<Path>>(path: T) -> Result<(), WriteError> {
+    Ok(fs::create_dir_all(path)?)
 }
 
 pub fn read_dir<T: AsRef<Path>>(path: T) -> Result<Vec<PathBuf>, ReadDirError> {
@@ -89,6 +224,56 @@ pub fn read_dir<T: AsRef<Path>>(path: T) -> Result<Vec<PathBuf>, ReadDirError> {
     Ok(entries)
 }
 
+/// Every file in the directory and its subdirectories, sorted. Symlinked directories are walked
+/// once, so that links back to an ancestor do not loop.
+pub fn walk_dir<T: AsRef<Path>>(path: T) -> Result<Vec<PathBuf>, ReadDirError> {
+    let mut files = vec![];
+    let mut pending = vec![path.as_ref().to_path_buf()];
+    let mut visited = HashSet::new();
+
+    while let Some(dir) = pending.pop() {
+        if !visited.insert(fs::canonicalize(&dir)?) {
+            continue;
+        }
+        for entry in read_dir(&dir)? {
+            match entry.is_dir() {
+                true => pending.push(entry),
+                false => files.push(entry),
+            }
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// The modification time of a file, or `None` if it does not exist.
+pub fn modified<T: AsRef<Path>>(path: T) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// The permission bits of a file, or `None` if it does not exist.
+pub fn mode<T: AsRef<Path>>(path: T) -> Option<u32> {
+    fs::metadata(path)
+        .map(|metadata| metadata.permissions().mode() & 0o7777)
+        .ok()
+}
+
+pub fn hostname() -> Option<String> {
+    let mut name = [0u8; 256];
+    // SAFETY: the pointer and length describe `name`, which outlives the call. A truncated name
+    // may lack the terminating NUL, which is checked for below rather than assumed.
+    let result = unsafe { libc::gethostname(name.as_mut_ptr().cast(), name.len()) };
+    if result != 0 {
+        return None;
+    }
+    let len = name.iter().position(|byte| *byte == 0)?;
+
+    String::from_utf8(name[..len].to_vec()).ok()
+}
+
 pub fn resolve_path(path: &Path) -> Option<PathBuf> {
     match path.strip_prefix("~") {
         Ok(subpath) => home::home_dir().map(|home| home.join(subpath)),
@@ -115,17 +300,159 @@ mod tests {
 
         let dir = tempdir().unwrap();
         let dir_path = dir.path();
-        let files = read_dir(&dir_path).unwrap();
+        let files = read_dir(dir_path).unwrap();
         assert_eq!(files.len(), 0);
 
         let file = dir_path.join("file.ext");
         write_to_file(&file, "Hello").unwrap();
         assert_eq!(read_file(&file).unwrap(), "Hello");
 
-        let files = read_dir(&dir_path).unwrap();
+        let files = read_dir(dir_path).unwrap();
         assert_eq!(files.len(), 1);
 
         assert_eq!(file.file_stem().unwrap(), "file");
         assert_eq!(file.extension().unwrap(), "ext");
+
+        assert!(!is_binary(&file).unwrap());
+        write_to_file(&file, [b'c', b'a', b'f', 0xe9, b'\n']).unwrap();
+        assert!(is_binary(&file).unwrap());
+        // A character cut in half where sniffing stops does not make the file binary.
+        let cut_off = "é".repeat(BINARY_SNIFF_LEN as usize);
+        write_to_file(&file, format!("a{cut_off}")).unwrap();
+        assert!(!is_binary(&file).unwrap());
+        write_to_file(&file, [0x89, b'P', b'N', b'G', 0x00]).unwrap();
+        assert!(is_binary(&file).unwrap());
+    }
+
+    #[test]
+    fn create_dirs() {
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let subdir = dir.path().join("sub/dir");
+        create_dir(&subdir).unwrap();
+        create_dir(&subdir).unwrap();
+        assert!(subdir.is_dir());
+    }
+
+    #[test]
+    fn symlinks() {
+        use std::os::unix::fs::symlink;
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path();
+        let file = dir_path.join("file");
+        write_to_file(&file, "Hello").unwrap();
+
+        // Writing through a symlink replaces the file it points to and keeps the link.
+        let link = dir_path.join("link");
+        symlink(&file, &link).unwrap();
+        write_to_file(&link, "Hi").unwrap();
+        assert!(link.is_symlink());
+        assert_eq!(read_file(&file).unwrap(), "Hi");
+        assert_eq!(read_dir(dir_path).unwrap().len(), 2);
+
+        let options = WriteOptions {
+            symlinks: Symlinks::Replace,
+            ..Default::default()
+        };
+        write_with_options(&link, "Hey", options).unwrap();
+        assert!(!link.is_symlink());
+        assert_eq!(read_file(&file).unwrap(), "Hi");
+    }
+
+    #[test]
+    fn walk() {
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path();
+        let file = dir_path.join("file.ext");
+        write_to_file(&file, "Hello").unwrap();
+
+        let nested = dir_path.join("sub/dir/image.png");
+        create_dir(nested.parent().unwrap()).unwrap();
+        write_to_file(&nested, [0xff, 0xd8]).unwrap();
+        assert_eq!(read_bytes(&nested).unwrap(), [0xff, 0xd8]);
+        assert!(matches!(read_file(&nested), Err(ReadError::InvalidData)));
+        assert_eq!(
+            walk_dir(dir_path).unwrap(),
+            vec![file.clone(), nested.clone()]
+        );
+
+        std::os::unix::fs::symlink(dir_path, dir_path.join("sub/loop")).unwrap();
+        assert_eq!(walk_dir(dir_path).unwrap(), vec![file, nested]);
+    }
+
+    #[test]
+    fn concurrent_writes() {
+        use std::thread;
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("file");
+        let contents = (0..8)
+            .map(|i| i.to_string().repeat(1 << 16))
+            .collect::<Vec<_>>();
+
+        thread::scope(|scope| {
+            for contents in &contents {
+                let file = &file;
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        write_to_file(file, contents).unwrap();
+                    }
+                });
+            }
+        });
+        assert!(contents.contains(&read_file(&file).unwrap()));
+        assert_eq!(read_dir(dir.path()).unwrap(), vec![file]);
+    }
+
+    #[test]
+    fn write_options() {
+        use std::os::unix::fs::symlink;
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("missing/dir/file");
+        assert!(matches!(
+            write_to_file(&file, ""),
+            Err(WriteError::DirectoryDoesNotExist)
+        ));
+
+        let options = WriteOptions {
+            create_dirs: true,
+            mode: Some(0o600),
+            ..Default::default()
+        };
+        write_with_options(&file, "secret", options).unwrap();
+        assert_eq!(read_file(&file).unwrap(), "secret");
+        let mode = fs::metadata(&file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // Rewriting keeps the mode, and dangling symlinks are followed as well.
+        write_to_file(&file, "public").unwrap();
+        let mode = fs::metadata(&file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // The mode is set before writing, which works even if it leaves the owner no write access.
+        let read_only = WriteOptions {
+            mode: Some(0o400),
+            ..options
+        };
+        write_with_options(&file, "read only", read_only).unwrap();
+        assert_eq!(read_file(&file).unwrap(), "read only");
+        assert_eq!(self::mode(&file), Some(0o400));
+
+        let link = dir.path().join("link");
+        symlink("missing/other", &link).unwrap();
+        write_with_options(&link, "linked", options).unwrap();
+        assert!(link.is_symlink());
+        assert_eq!(
+            read_file(dir.path().join("missing/other")).unwrap(),
+            "linked"
+        );
     }
 }
@@ -0,0 +1,59 @@
+use crate::color::Color;
+use crate::theme::{AnsiColors, Theme};
+
+const ANSI_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// Renders the colors of a theme as true color swatches.
+pub fn preview(theme: &Theme) -> String {
+    let special = &theme.special;
+    let mut lines = vec![
+        swatch("background", special.background),
+        swatch("foreground", special.foreground),
+        swatch("cursor", special.cursor),
+    ];
+    lines.extend(ansi_swatches("normal", &theme.normal));
+    lines.extend(ansi_swatches("bright", &theme.bright));
+
+    lines.join("\n")
+}
+
+fn ansi_swatches<'a>(group: &'a str, colors: &'a AnsiColors) -> impl Iterator<Item = String> + 'a {
+    ANSI_NAMES
+        .iter()
+        .zip(colors.colors())
+        .map(move |(name, color)| swatch(&format!("{group}.{name}"), color))
+}
+
+fn swatch(name: &str, color: Color) -> String {
+    let [red, green, blue] = color.to_rgb();
+
+    format!(
+        "\x1b[48;2;{red};{green};{blue}m      \x1b[0m {name:<16} {}",
+        color.to_hex()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swatches() {
+        let theme = Theme::from_palette(&[
+            Color::from_hex("#000000").unwrap(),
+            Color::from_hex("#ffffff").unwrap(),
+        ]);
+        let preview = preview(&theme);
+        let lines = preview.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 19);
+        assert_eq!(
+            lines[1],
+            "\x1b[48;2;255;255;255m      \x1b[0m foreground       #ffffff"
+        );
+        assert!(lines[10].contains("normal.white"));
+        assert!(lines[18].contains("bright.white"));
+    }
+}
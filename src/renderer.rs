@@ -1,14 +1,28 @@
 use crate::color::Color;
+use crate::os;
 use handlebars::{
-    handlebars_helper, Handlebars, RenderError as HbRenderError,
-    RenderErrorReason as HbRenderErrorReason,
+    handlebars_helper, Context as HbContext, Handlebars, Helper, HelperResult, Output,
+    RenderContext, RenderError as HbRenderError, RenderErrorReason as HbRenderErrorReason,
+    TemplateError as HbTemplateError,
 };
-pub use serde::Serialize;
-pub use serde_json::json as context;
+pub use serde::{Serialize, Serializer};
+pub use serde_json::{json as context, Value as Context};
 pub use serde_yaml::Value;
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+/// The most colors `gradient` and `shades` will generate.
+const MAX_RAMP_LENGTH: usize = 256;
+
+fn ramp_too_long(helper: &str, param: &str) -> HbRenderError {
+    HbRenderErrorReason::Other(format!(
+        "helper '{helper}' expected at most {MAX_RAMP_LENGTH} for param '{param}'"
+    ))
+    .into()
+}
+
 handlebars_helper!(env: |key: String| std::env::var(&key).unwrap_or_default());
 handlebars_helper!(eq: |a: String, b: String| a == b);
 handlebars_helper!(hex: |number: u8| format!("{:x}", number));
@@ -21,32 +35,105 @@ handlebars_helper!(mul: |multiplicand: f32, multiplier: f32| {
 handlebars_helper!(add: |augend: f32, addend: f32| augend + addend);
 handlebars_helper!(sub: |minuend: f32, subtrahend: f32| minuend - subtrahend);
 handlebars_helper!(int: |number: f32| number as u32);
-handlebars_helper!(lighten: |color: String, factor: f32| {
-    let color = Color::from_hex(&color).unwrap();
-    color.lighten(factor).to_hex()
+// Colors are deserialized from their parameters, so invalid colors surface as a type mismatch.
+handlebars_helper!(lighten: |color: Color, factor: f32| color.lighten(factor).to_string());
+handlebars_helper!(darken: |color: Color, factor: f32| color.darken(factor).to_string());
+handlebars_helper!(shift_hue: |color: Color, amount: f32| color.shift_hue(amount).to_string());
+handlebars_helper!(saturate: |color: Color, factor: f32| color.saturate(factor).to_string());
+handlebars_helper!(ok_lighten: |color: Color, factor: f32| color.ok_lighten(factor).to_string());
+handlebars_helper!(ok_darken: |color: Color, factor: f32| color.ok_darken(factor).to_string());
+handlebars_helper!(ok_saturate: |color: Color, factor: f32| {
+    color.ok_saturate(factor).to_string()
 });
-handlebars_helper!(darken: |color: String, factor: f32| {
-    let color = Color::from_hex(&color).unwrap();
-    color.darken(factor).to_hex()
+handlebars_helper!(ok_shift_hue: |color: Color, amount: f32| {
+    color.ok_shift_hue(amount).to_string()
 });
-handlebars_helper!(shift_hue: |color: String, amount: f32| {
-    let color = Color::from_hex(&color).unwrap();
-    color.shift_hue(amount).to_hex()
+// The factor can be passed either as the third parameter or as `factor=`.
+handlebars_helper!(mix: |color: Color, other: Color, {factor: f32 = 0.5}, *args| {
+    let factor = match args.get(2) {
+        Some(factor) => match factor.as_f64() {
+            Some(factor) => factor as f32,
+            None => {
+                return Err(HbRenderErrorReason::ParamTypeMismatchForName(
+                    "mix",
+                    "factor".to_string(),
+                    "f32".to_string(),
+                )
+                .into())
+            }
+        },
+        None => factor,
+    };
+
+    color.mix(other, factor).to_string()
+});
+handlebars_helper!(gradient: |color: Color, other: Color, steps: usize| {
+    if steps > MAX_RAMP_LENGTH {
+        return Err(ramp_too_long("gradient", "steps"));
+    }
+
+    color.gradient(other, steps).iter().map(Color::to_string).collect::<Vec<String>>()
+});
+handlebars_helper!(shades: |color: Color, count: usize| {
+    if count > MAX_RAMP_LENGTH {
+        return Err(ramp_too_long("shades", "count"));
+    }
+
+    color.shades(count).iter().map(Color::to_string).collect::<Vec<String>>()
 });
-handlebars_helper!(saturate: |color: String, factor: f32| {
-    let color = Color::from_hex(&color).unwrap();
-    color.saturate(factor).to_hex()
+handlebars_helper!(blend: |color: Color, background: Color| color.blend(background).to_string());
+handlebars_helper!(contrast: |color: Color, other: Color| {
+    format!("{:.2}", color.contrast(other))
 });
-handlebars_helper!(to_rgba: |color: String, alpha: f32| {
-    let color = Color::from_hex(&color).unwrap();
-    color.to_rgba(alpha)
+handlebars_helper!(readable: |color: Color, background: Color, {ratio: f32 = 4.5}| {
+    color.readable(background, ratio).to_string()
 });
+handlebars_helper!(get_alpha: |color: Color| color.alpha());
+handlebars_helper!(with_alpha: |color: Color, alpha: f32| color.with_alpha(alpha).to_string());
+handlebars_helper!(to_hex: |color: Color| color.to_hex());
+handlebars_helper!(to_hex_rgba: |color: Color| color.to_hex_rgba());
+handlebars_helper!(to_hex_argb: |color: Color| color.to_hex_argb());
+handlebars_helper!(to_int_argb: |color: Color| color.to_int_argb());
 handlebars_helper!(strip: |color: String| {
     color.strip_prefix('#').unwrap_or(&color).to_string()
 });
 
+/// Formats a color as `rgba()` using its own alpha, unless one is passed as the second parameter.
+fn to_rgba(
+    helper: &Helper,
+    _: &Handlebars,
+    _: &HbContext,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let mismatch = |name: &str, param_type: &str| {
+        HbRenderErrorReason::ParamTypeMismatchForName(
+            "toRgba",
+            name.to_string(),
+            param_type.to_string(),
+        )
+    };
+
+    let color = helper
+        .param(0)
+        .ok_or(HbRenderErrorReason::ParamNotFoundForIndex("toRgba", 0))?;
+    let color: Color =
+        serde_json::from_value(color.value().clone()).map_err(|_| mismatch("color", "Color"))?;
+    let color = match helper.param(1) {
+        Some(alpha) => {
+            let alpha = alpha.value().as_f64().ok_or(mismatch("alpha", "f32"))?;
+            color.with_alpha(alpha as f32)
+        }
+        None => color,
+    };
+    out.write(&color.to_rgba())?;
+
+    Ok(())
+}
+
 #[derive(Error, Debug)]
 pub struct RenderError {
+    file: Option<PathBuf>,
     line: Option<usize>,
     column: Option<usize>,
     reason: String,
@@ -74,6 +161,11 @@ impl From<HbRenderError> for RenderError {
                         .unwrap_or_default()
                 )
             }
+            HbRenderErrorReason::ParamTypeMismatchForName(helper, param, param_type)
+                if param_type == "Color" =>
+            {
+                format!("helper '{helper}' expected a valid color for param '{param}'")
+            }
             HbRenderErrorReason::ParamTypeMismatchForName(helper, param, param_type) => {
                 format!("helper '{helper}' expected '{param_type}' value for param '{param}'")
             }
@@ -83,6 +175,7 @@ impl From<HbRenderError> for RenderError {
         };
 
         RenderError {
+            file: None,
             line,
             column,
             reason,
@@ -90,6 +183,19 @@ impl From<HbRenderError> for RenderError {
     }
 }
 
+impl From<HbTemplateError> for RenderError {
+    fn from(error: HbTemplateError) -> Self {
+        let (line, column) = error.pos().unzip();
+
+        RenderError {
+            file: None,
+            line,
+            column,
+            reason: error.reason().to_string(),
+        }
+    }
+}
+
 impl Display for RenderError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         let location = self
@@ -104,17 +210,26 @@ impl Display for RenderError {
             })
             .unwrap_or_default();
 
-        write!(f, "{}{location}", self.reason)
+        let file = self
+            .file
+            .as_ref()
+            .map(|file| format!(" in '{}'", file.display()))
+            .unwrap_or_default();
+
+        write!(f, "{}{file}{location}", self.reason)
     }
 }
 
-pub struct Renderer<'a, T> {
-    registry: Handlebars<'a>,
-    context: &'a T,
+/// Renders templates against a context. Templates registered by name stay compiled, so that they
+/// can be rendered again, for instance with another context, without being parsed again.
+pub struct Renderer<T> {
+    registry: Handlebars<'static>,
+    context: T,
+    partials: HashMap<String, PathBuf>,
 }
 
-impl<'a, T: Serialize> Renderer<'a, T> {
-    pub fn new(context: &'a T) -> Self {
+impl<T: Serialize> Renderer<T> {
+    pub fn new(context: T) -> Self {
         let mut registry = Handlebars::new();
 
         registry.set_strict_mode(true);
@@ -126,21 +241,116 @@ impl<'a, T: Serialize> Renderer<'a, T> {
         registry.register_helper("lighten", Box::new(lighten));
         registry.register_helper("shiftHue", Box::new(shift_hue));
         registry.register_helper("saturate", Box::new(saturate));
+        registry.register_helper("okLighten", Box::new(ok_lighten));
+        registry.register_helper("okDarken", Box::new(ok_darken));
+        registry.register_helper("okSaturate", Box::new(ok_saturate));
+        registry.register_helper("okShiftHue", Box::new(ok_shift_hue));
+        registry.register_helper("mix", Box::new(mix));
+        registry.register_helper("blend", Box::new(blend));
+        registry.register_helper("gradient", Box::new(gradient));
+        registry.register_helper("shades", Box::new(shades));
         registry.register_helper("toRgba", Box::new(to_rgba));
+        registry.register_helper("contrast", Box::new(contrast));
+        registry.register_helper("readable", Box::new(readable));
+        registry.register_helper("alpha", Box::new(get_alpha));
+        registry.register_helper("withAlpha", Box::new(with_alpha));
+        registry.register_helper("toHex", Box::new(to_hex));
+        registry.register_helper("toHexRgba", Box::new(to_hex_rgba));
+        registry.register_helper("toHexArgb", Box::new(to_hex_argb));
+        registry.register_helper("toIntArgb", Box::new(to_int_argb));
         registry.register_helper("strip", Box::new(strip));
         registry.register_helper("add", Box::new(add));
         registry.register_helper("sub", Box::new(sub));
         registry.register_helper("env", Box::new(env));
         registry.register_helper("eq", Box::new(eq));
 
-        Renderer { registry, context }
+        Renderer {
+            registry,
+            context,
+            partials: HashMap::new(),
+        }
+    }
+
+    pub fn set_context(&mut self, context: T) {
+        self.context = context;
+    }
+
+    /// Parses the template and registers it as `name`, replacing any previous version.
+    pub fn register(&mut self, name: &str, template: &str) -> Result<(), RenderError> {
+        Ok(self
+            .registry
+            .register_template_string(&template_key(name), template)?)
+    }
+
+    /// Parses the file and registers it as a partial that templates include with `{{> name}}`.
+    /// Errors within the partial, whether now or while rendering, name its file.
+    pub fn register_partial(&mut self, name: &str, path: &Path) -> Result<(), RenderError> {
+        let error = |reason: String| RenderError {
+            file: Some(path.to_path_buf()),
+            line: None,
+            column: None,
+            reason,
+        };
+        if let Some(other) = self.partials.get(name).filter(|other| *other != path) {
+            let reason = format!(
+                "partial '{name}' is already defined in '{}'",
+                other.display()
+            );
+            return Err(error(reason));
+        }
+        let source = os::read_file(path).map_err(|err| error(err.to_string()))?;
+
+        self.registry
+            .register_partial(name, source)
+            .map_err(|err| RenderError {
+                file: Some(path.to_path_buf()),
+                ..err.into()
+            })?;
+        self.partials.insert(name.to_string(), path.to_path_buf());
+
+        Ok(())
+    }
+
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.registry.has_template(&template_key(name))
+    }
+
+    pub fn render_registered(&self, name: &str) -> Result<String, RenderError> {
+        self.render_registered_with(name, &self.context)
+    }
+
+    /// Renders a registered template against another context than the renderer's own.
+    pub fn render_registered_with<U: Serialize>(
+        &self,
+        name: &str,
+        context: &U,
+    ) -> Result<String, RenderError> {
+        self.registry
+            .render(&template_key(name), context)
+            .map_err(|err| {
+                let partial = err
+                    .template_name
+                    .as_ref()
+                    .and_then(|t| self.partials.get(t));
+
+                RenderError {
+                    file: partial.cloned(),
+                    ..err.into()
+                }
+            })
     }
 
     pub fn render(&self, template: &str) -> Result<String, RenderError> {
-        Ok(self.registry.render_template(template, self.context)?)
+        Ok(self.registry.render_template(template, &self.context)?)
     }
 }
 
+/// Templates are registered under a leading `/`, which partial names, being file names, cannot
+/// contain, so that a template never replaces a partial or is included as one.
+fn template_key(name: &str) -> String {
+    format!("/{name}")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -156,4 +366,173 @@ mod tests {
         assert_eq!(renderer.render("name: {{name}}").unwrap(), "name: John");
         assert_eq!(renderer.render("age: {{age}}").unwrap(), "age: 21");
     }
+
+    #[test]
+    fn registered() {
+        let mut renderer = Renderer::new(context!({ "name": "John" }));
+        assert!(!renderer.is_registered("valid"));
+        renderer.register("valid", "name: {{name}}").unwrap();
+        assert!(renderer.is_registered("valid"));
+        assert_eq!(renderer.render_registered("valid").unwrap(), "name: John");
+
+        // Compiled templates are kept when the context changes.
+        renderer.set_context(context!({ "name": "Jane" }));
+        assert_eq!(renderer.render_registered("valid").unwrap(), "name: Jane");
+        assert_eq!(
+            renderer
+                .render_registered_with("valid", &context!({ "name": "Joe" }))
+                .unwrap(),
+            "name: Joe"
+        );
+
+        let err = renderer.register("invalid", "\n{{#if name}}").unwrap_err();
+        assert_eq!(err.line, Some(2));
+        assert!(renderer.render_registered("invalid").is_err());
+    }
+
+    #[test]
+    fn partials() {
+        let dir = tempfile::tempdir().unwrap();
+        let partial = dir.path().join("palette.hbs");
+        std::fs::write(&partial, "name: {{name}}").unwrap();
+
+        let mut renderer = Renderer::new(context!({ "name": "John" }));
+        renderer.register_partial("palette", &partial).unwrap();
+        renderer.register("template", "{{> palette}}!").unwrap();
+        assert_eq!(
+            renderer.render_registered("template").unwrap(),
+            "name: John!"
+        );
+
+        renderer.set_context(context!({}));
+        let err = renderer.render_registered("template").unwrap_err();
+        assert_eq!(err.file.as_deref(), Some(partial.as_path()));
+        assert!(err.to_string().contains("palette.hbs"));
+
+        // Templates and partials with the same name do not replace each other.
+        renderer.set_context(context!({ "name": "John" }));
+        renderer.register("palette", "{{> palette}}!").unwrap();
+        assert_eq!(
+            renderer.render_registered("template").unwrap(),
+            "name: John!"
+        );
+
+        let other = dir.path().join("palette.rasi");
+        std::fs::write(&other, "").unwrap();
+        let err = renderer.register_partial("palette", &other).unwrap_err();
+        assert_eq!(err.file.as_deref(), Some(other.as_path()));
+        assert!(err.to_string().contains("already defined"));
+
+        std::fs::write(&partial, "{{#if name}}").unwrap();
+        let err = renderer.register_partial("palette", &partial).unwrap_err();
+        assert_eq!(err.file.as_deref(), Some(partial.as_path()));
+    }
+
+    #[test]
+    fn color_helpers() {
+        let context = context!({
+            "color": "#000000",
+            "typo": "#12345",
+        });
+        let renderer = Renderer::new(&context);
+
+        assert_eq!(renderer.render("{{lighten color 0.5}}").unwrap(), "#808080");
+        assert_eq!(
+            renderer
+                .render("{{darken 'hsl(0, 0%, 100%)' 0.5}}")
+                .unwrap(),
+            "#808080"
+        );
+        assert_eq!(
+            renderer
+                .render("{{lighten typo 0.5}}")
+                .unwrap_err()
+                .to_string(),
+            "helper 'lighten' expected a valid color for param 'color' at line 1 column 1"
+        );
+        assert!(renderer.render("{{toRgba typo 0.5}}").is_err());
+        assert_eq!(
+            renderer.render("{{contrast color '#fff'}}").unwrap(),
+            "21.00"
+        );
+        assert_eq!(
+            renderer
+                .render("{{readable color color ratio=21}}")
+                .unwrap(),
+            "#ffffff"
+        );
+    }
+
+    #[test]
+    fn ramp_helpers() {
+        let context = context!({ "from": "#000000", "to": "#ffffff" });
+        let renderer = Renderer::new(&context);
+
+        assert_eq!(
+            renderer
+                .render("{{#each (gradient from to 3)}}{{this}} {{/each}}")
+                .unwrap(),
+            "#000000 #636363 #ffffff "
+        );
+        assert_eq!(
+            renderer
+                .render("{{#each (shades to 2)}}{{@index}}:{{this}} {{/each}}")
+                .unwrap(),
+            "0:#363636 1:#949494 "
+        );
+        assert_eq!(
+            renderer
+                .render("{{#each (shades to 100000000)}}{{this}}{{/each}}")
+                .unwrap_err()
+                .to_string(),
+            "helper 'shades' expected at most 256 for param 'count' at line 1 column 1"
+        );
+        assert!(renderer
+            .render("{{#each (gradient from to 100000000)}}{{this}}{{/each}}")
+            .is_err());
+    }
+
+    #[test]
+    fn mix_helper() {
+        let context = context!({ "from": "#000000", "to": "#ffffff" });
+        let renderer = Renderer::new(&context);
+
+        let half = renderer.render("{{mix from to}}").unwrap();
+        assert_eq!(renderer.render("{{mix from to factor=0.5}}").unwrap(), half);
+        assert_eq!(renderer.render("{{mix from to 0.5}}").unwrap(), half);
+        assert_eq!(renderer.render("{{mix from to 0}}").unwrap(), "#000000");
+        assert_eq!(
+            renderer.render("{{mix from to factor=1}}").unwrap(),
+            "#ffffff"
+        );
+        assert!(renderer.render("{{mix from to 'half'}}").is_err());
+    }
+
+    #[test]
+    fn alpha_helpers() {
+        let context = context!({ "color": "#ff880080" });
+        let renderer = Renderer::new(&context);
+
+        assert_eq!(
+            renderer.render("{{toRgba color}}").unwrap(),
+            "rgba(255, 136, 0, 0.50)"
+        );
+        assert_eq!(
+            renderer.render("{{toRgba color 1}}").unwrap(),
+            "rgba(255, 136, 0, 1.00)"
+        );
+        assert_eq!(renderer.render("{{toHex color}}").unwrap(), "#ff8800");
+        assert_eq!(renderer.render("{{toHexArgb color}}").unwrap(), "#80ff8800");
+        assert_eq!(
+            renderer
+                .render("{{toIntArgb (withAlpha color 1)}}")
+                .unwrap(),
+            "0xffff8800"
+        );
+        assert_eq!(renderer.render("{{lighten color 0}}").unwrap(), "#ff880080");
+        assert_eq!(
+            renderer.render("{{toRgba typo}}").unwrap_err().to_string(),
+            "helper 'toRgba' expected a valid color for param 'color' at line 1 column 1"
+        );
+    }
 }
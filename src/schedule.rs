@@ -0,0 +1,263 @@
+use crate::yaml_parser::{de, Deserialize, Deserializer};
+use std::f64::consts::PI;
+use thiserror::Error;
+
+const MINUTES_PER_DAY: f64 = 24.0 * 60.0;
+
+#[derive(Error, Debug)]
+pub enum ScheduleError {
+    #[error("no themes scheduled")]
+    Empty,
+
+    #[error("sunrise and sunset need a latitude and longitude")]
+    MissingLocation,
+}
+
+/// When a scheduled theme starts, either at a fixed time or relative to the sun.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Start {
+    Time(f64),
+    Sunrise(f64),
+    Sunset(f64),
+}
+
+impl TryFrom<&str> for Start {
+    type Error = String;
+
+    /// Parses `HH:MM`, `sunrise` or `sunset`, where the latter two take an optional offset like
+    /// `sunset-01:30`.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid start '{value}': should be HH:MM, sunrise or sunset");
+        let minutes = |time: &str| {
+            let (hours, minutes) = time.split_once(':')?;
+            let (hours, minutes) = (hours.parse::<u32>().ok()?, minutes.parse::<u32>().ok()?);
+
+            (hours < 24 && minutes < 60).then_some((hours * 60 + minutes) as f64)
+        };
+
+        let (event, offset) = match value.find(['+', '-']) {
+            Some(index) => {
+                let offset = minutes(&value[index + 1..]).ok_or_else(invalid)?;
+                match &value[index..index + 1] {
+                    "-" => (&value[..index], -offset),
+                    _ => (&value[..index], offset),
+                }
+            }
+            None => (value, 0.0),
+        };
+
+        match event.trim() {
+            "sunrise" => Ok(Start::Sunrise(offset)),
+            "sunset" => Ok(Start::Sunset(offset)),
+            time if offset == 0.0 => minutes(time).map(Start::Time).ok_or_else(invalid),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Start {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let start = String::deserialize(deserializer)?;
+
+        Start::try_from(start.as_str()).map_err(de::Error::custom)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Entry {
+    pub from: Start,
+    pub theme: String,
+}
+
+#[derive(Deserialize)]
+pub struct Schedule {
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub themes: Vec<Entry>,
+}
+
+/// The local time of day along with what is needed to place the sun.
+#[derive(Clone, Copy, Debug)]
+pub struct LocalTime {
+    pub day_of_year: u32,
+    pub minutes: f64,
+    pub utc_offset: f64,
+}
+
+impl LocalTime {
+    pub fn now() -> Self {
+        // SAFETY: `time` accepts a null pointer, in which case it only returns the time.
+        let now = unsafe { libc::time(std::ptr::null_mut()) };
+        // SAFETY: `tm` is a plain C struct of integers and a pointer, for which all zeros is valid.
+        let mut tm = unsafe { std::mem::zeroed::<libc::tm>() };
+        // SAFETY: both pointers come from references to live locals. `localtime_r` only writes
+        // to `tm`, unlike `localtime` which shares a static buffer between threads.
+        unsafe { libc::localtime_r(&now, &mut tm) };
+
+        LocalTime {
+            day_of_year: tm.tm_yday as u32 + 1,
+            minutes: (tm.tm_hour * 60 + tm.tm_min) as f64,
+            utc_offset: tm.tm_gmtoff as f64 / 60.0,
+        }
+    }
+}
+
+impl Schedule {
+    /// The theme whose window contains `time`. Times before the first window of the day belong to
+    /// the last window of the previous day.
+    pub fn theme_at(&self, time: LocalTime) -> Result<&str, ScheduleError> {
+        let starts = self
+            .themes
+            .iter()
+            .filter_map(|entry| {
+                let start = self.minutes(entry.from, time).transpose()?;
+                Some(start.map(|start| (start, entry.theme.as_str())))
+            })
+            .collect::<Result<Vec<(f64, &str)>, ScheduleError>>()?;
+        let by_start = |a: &&(f64, &str), b: &&(f64, &str)| a.0.total_cmp(&b.0);
+
+        starts
+            .iter()
+            .filter(|(start, _)| *start <= time.minutes)
+            .max_by(by_start)
+            .or_else(|| starts.iter().max_by(by_start))
+            .map(|(_, theme)| *theme)
+            .ok_or(ScheduleError::Empty)
+    }
+
+    /// The minute of the day at which an entry starts, or `None` when it does not start today.
+    fn minutes(&self, start: Start, time: LocalTime) -> Result<Option<f64>, ScheduleError> {
+        let (event, offset) = match start {
+            Start::Time(minutes) => return Ok(Some(minutes)),
+            Start::Sunrise(offset) => (-1.0, offset),
+            Start::Sunset(offset) => (1.0, offset),
+        };
+        let (Some(latitude), Some(longitude)) = (self.latitude, self.longitude) else {
+            return Err(ScheduleError::MissingLocation);
+        };
+
+        let hour_angle = match hour_angle(latitude, time.day_of_year) {
+            Ok(hour_angle) => hour_angle,
+            // During polar day the sunrise entries start at midnight and the sunset ones never do,
+            // and the other way around during polar night.
+            Err(polar) => return Ok(((polar == Polar::Day) == (event < 0.0)).then_some(0.0)),
+        };
+        let utc = 720.0 - 4.0 * (longitude - event * hour_angle) - equation_of_time(time);
+
+        Ok(Some(
+            (utc + time.utc_offset + offset).rem_euclid(MINUTES_PER_DAY),
+        ))
+    }
+}
+
+/// The fractional year in radians, following NOAA's solar calculations.
+fn fractional_year(day_of_year: u32) -> f64 {
+    2.0 * PI / 365.0 * (day_of_year as f64 - 1.0)
+}
+
+fn equation_of_time(time: LocalTime) -> f64 {
+    let year = fractional_year(time.day_of_year);
+
+    229.18
+        * (0.000075 + 0.001868 * year.cos()
+            - 0.032077 * year.sin()
+            - 0.014615 * (2.0 * year).cos()
+            - 0.040849 * (2.0 * year).sin())
+}
+
+/// Whether the sun stays above or below the horizon for the whole day.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Polar {
+    Day,
+    Night,
+}
+
+/// The hour angle of sunrise in degrees, unless the sun neither rises nor sets.
+fn hour_angle(latitude: f64, day_of_year: u32) -> Result<f64, Polar> {
+    let year = fractional_year(day_of_year);
+    let declination = 0.006918 - 0.399912 * year.cos() + 0.070257 * year.sin()
+        - 0.006758 * (2.0 * year).cos()
+        + 0.000907 * (2.0 * year).sin()
+        - 0.002697 * (3.0 * year).cos()
+        + 0.00148 * (3.0 * year).sin();
+    let latitude = latitude.to_radians();
+
+    let cos = 90.833_f64.to_radians().cos() / (latitude.cos() * declination.cos())
+        - latitude.tan() * declination.tan();
+
+    match cos {
+        cos if cos < -1.0 => Err(Polar::Day),
+        cos if cos > 1.0 => Err(Polar::Night),
+        cos => Ok(cos.acos().to_degrees()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml_parser::YamlParser;
+
+    fn at(day_of_year: u32, hours: u32, minutes: u32) -> LocalTime {
+        LocalTime {
+            day_of_year,
+            minutes: (hours * 60 + minutes) as f64,
+            utc_offset: 0.0,
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Start::try_from("07:30"), Ok(Start::Time(450.0)));
+        assert_eq!(Start::try_from("sunrise"), Ok(Start::Sunrise(0.0)));
+        assert_eq!(Start::try_from("sunset-01:30"), Ok(Start::Sunset(-90.0)));
+        assert_eq!(Start::try_from("sunrise+00:15"), Ok(Start::Sunrise(15.0)));
+        assert!(Start::try_from("25:00").is_err());
+        assert!(Start::try_from("07:30+01:00").is_err());
+        assert!(Start::try_from("noon").is_err());
+    }
+
+    #[test]
+    fn theme_at() {
+        let schedule: Schedule = YamlParser::parse(
+            "
+latitude: 0
+longitude: 0
+themes:
+  - from: sunrise
+    theme: day
+  - from: '12:00'
+    theme: noon
+  - from: sunset
+    theme: night
+",
+        )
+        .unwrap();
+
+        // Around the equinox the sun rises at about 06:00 and sets at about 18:00 on the equator.
+        assert_eq!(schedule.theme_at(at(80, 3, 0)).unwrap(), "night");
+        assert_eq!(schedule.theme_at(at(80, 6, 30)).unwrap(), "day");
+        assert_eq!(schedule.theme_at(at(80, 13, 0)).unwrap(), "noon");
+        assert_eq!(schedule.theme_at(at(80, 18, 30)).unwrap(), "night");
+
+        let polar = Schedule {
+            latitude: Some(80.0),
+            ..schedule
+        };
+        // The sun stays up all day in June and down all day in December.
+        assert_eq!(polar.theme_at(at(172, 3, 0)).unwrap(), "day");
+        assert_eq!(polar.theme_at(at(172, 13, 0)).unwrap(), "noon");
+        assert_eq!(polar.theme_at(at(355, 3, 0)).unwrap(), "night");
+        assert_eq!(polar.theme_at(at(355, 13, 0)).unwrap(), "noon");
+        let south = Schedule {
+            latitude: Some(-80.0),
+            ..polar
+        };
+        assert_eq!(south.theme_at(at(172, 3, 0)).unwrap(), "night");
+
+        let fixed: Schedule = YamlParser::parse("themes: [{from: sunset, theme: night}]").unwrap();
+        assert!(matches!(
+            fixed.theme_at(at(1, 12, 0)),
+            Err(ScheduleError::MissingLocation)
+        ));
+    }
+}
@@ -0,0 +1,274 @@
+use crate::color::Color;
+use crate::os::{self, Path, ReadDirError, WriteError};
+use crate::theme::Theme;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::time::{Duration, Instant};
+
+const SEQUENCES_FILE: &str = "sequences";
+const PTS_DIR: &str = "/dev/pts";
+/// How long to wait for a terminal to take the rest of a sequence it has only partly accepted.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Escape sequences which set the palette of a running terminal.
+pub struct Sequences {
+    sequences: Vec<String>,
+}
+
+impl Sequences {
+    pub fn new(theme: &Theme) -> Self {
+        let special = &theme.special;
+        let mut sequences = theme
+            .normal
+            .colors()
+            .into_iter()
+            .chain(theme.bright.colors())
+            .enumerate()
+            .map(|(index, color)| osc(&format!("4;{index}"), color))
+            .collect::<Vec<_>>();
+
+        sequences.push(osc("10", special.foreground));
+        sequences.push(osc("11", special.background));
+        sequences.push(osc("12", special.cursor));
+        // rxvt uses this one for the border around the terminal.
+        sequences.push(osc("708", special.background));
+
+        Sequences { sequences }
+    }
+
+    fn contents(&self) -> String {
+        self.sequences.concat()
+    }
+
+    /// Writes the sequences to every terminal in `pts_dir` owned by the current user, returning
+    /// how many of them were updated. Terminals that cannot take the sequences right away, e.g.
+    /// because they are stopped, are skipped rather than waited for. Sequences are written one at
+    /// a time so that a skipped terminal is never left with half of one.
+    pub fn send(&self, pts_dir: &Path) -> Result<usize, ReadDirError> {
+        // SAFETY: getuid takes no arguments and always succeeds.
+        let uid = unsafe { libc::getuid() };
+        let terminals = os::read_dir(pts_dir)?
+            .into_iter()
+            .filter(|terminal| {
+                let is_pty = terminal
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.parse::<u32>().is_ok());
+                let is_owned = fs::metadata(terminal).is_ok_and(|meta| meta.uid() == uid);
+
+                is_pty && is_owned
+            })
+            .filter(|terminal| {
+                // A full buffer fails the write with EAGAIN instead of blocking. Not becoming the
+                // controlling terminal of the process keeps its signals away from us.
+                OpenOptions::new()
+                    .write(true)
+                    .custom_flags(libc::O_NONBLOCK | libc::O_NOCTTY)
+                    .open(terminal)
+                    .and_then(|mut file| {
+                        self.sequences
+                            .iter()
+                            .try_for_each(|sequence| write_sequence(&mut file, sequence))
+                    })
+                    .is_ok()
+            })
+            .count();
+
+        Ok(terminals)
+    }
+
+    pub fn send_all(&self) -> Result<usize, ReadDirError> {
+        self.send(Path::new(PTS_DIR))
+    }
+
+    /// Saves the sequences into the cache directory so that new shells can `cat` them.
+    pub fn save(&self, cache_dir: &Path) -> Result<(), WriteError> {
+        os::create_dir(cache_dir)?;
+        os::write_to_file(cache_dir.join(SEQUENCES_FILE), self.contents())
+    }
+}
+
+/// Writes a whole sequence to a non-blocking terminal. A terminal whose buffer is full fails
+/// before anything is written, while one that takes only part of the sequence is waited on until
+/// it takes the rest, as a truncated sequence would garble whatever it prints next.
+fn write_sequence(file: &mut File, sequence: &str) -> io::Result<()> {
+    let mut remaining = sequence.as_bytes();
+    let deadline = Instant::now() + DRAIN_TIMEOUT;
+
+    while !remaining.is_empty() {
+        match file.write(remaining) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(written) => remaining = &remaining[written..],
+            Err(error) if error.kind() == ErrorKind::Interrupted => {}
+            Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                if remaining.len() == sequence.len() {
+                    return Err(error);
+                }
+
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                if timeout.is_zero() {
+                    return Err(error);
+                }
+
+                let mut poll = libc::pollfd {
+                    fd: file.as_raw_fd(),
+                    events: libc::POLLOUT,
+                    revents: 0,
+                };
+                // SAFETY: poll points to a single valid pollfd for the duration of the call.
+                unsafe { libc::poll(&mut poll, 1, timeout.as_millis() as libc::c_int) };
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(())
+}
+
+fn osc(code: &str, color: Color) -> String {
+    format!("\x1b]{code};{}\x1b\\", color.to_hex())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::{AnsiColors, SpecialColors};
+
+    fn theme() -> Theme {
+        let ansi = || AnsiColors {
+            black: Color::new(0x00, 0x00, 0x00),
+            red: Color::new(0xff, 0x00, 0x00),
+            green: Color::new(0x00, 0xff, 0x00),
+            yellow: Color::new(0xff, 0xff, 0x00),
+            blue: Color::new(0x00, 0x00, 0xff),
+            magenta: Color::new(0xff, 0x00, 0xff),
+            cyan: Color::new(0x00, 0xff, 0xff),
+            white: Color::new(0xff, 0xff, 0xff),
+        };
+
+        Theme {
+            special: SpecialColors {
+                background: Color::new(0x11, 0x11, 0x11),
+                foreground: Color::new(0xee, 0xee, 0xee),
+                cursor: Color::new(0xdd, 0xdd, 0xdd),
+            },
+            normal: ansi(),
+            bright: ansi(),
+            base: Default::default(),
+        }
+    }
+
+    #[test]
+    fn contents() {
+        let sequences = Sequences::new(&theme());
+
+        let contents = sequences.contents();
+
+        assert!(contents.starts_with("\x1b]4;0;#000000\x1b\\"));
+        assert!(contents.contains("\x1b]4;9;#ff0000\x1b\\"));
+        assert!(contents.contains("\x1b]10;#eeeeee\x1b\\"));
+        assert!(contents.contains("\x1b]11;#111111\x1b\\"));
+        assert!(contents.contains("\x1b]12;#dddddd\x1b\\"));
+        assert!(contents.ends_with("\x1b]708;#111111\x1b\\"));
+    }
+
+    #[test]
+    fn write_whole_sequences() {
+        use std::io::Read;
+        use std::os::fd::FromRawFd;
+        use std::thread;
+
+        let pipe = || {
+            let mut fds = [0; 2];
+            // SAFETY: fds has room for the two descriptors pipe returns.
+            assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+            // SAFETY: fds[1] is the open write end of the pipe.
+            unsafe { libc::fcntl(fds[1], libc::F_SETFL, libc::O_NONBLOCK) };
+            // SAFETY: both descriptors are open and owned by nothing else.
+            unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) }
+        };
+
+        // A sequence that does not fit yet is finished once the reader catches up.
+        let (mut reader, mut writer) = pipe();
+        let filler = vec![b'x'; 60_000];
+        writer.write_all(&filler).unwrap();
+        let sequence = "y".repeat(8192);
+        let drained = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            let mut received = Vec::new();
+            reader.read_to_end(&mut received).unwrap();
+            received
+        });
+        write_sequence(&mut writer, &sequence).unwrap();
+        drop(writer);
+        let received = drained.join().unwrap();
+        assert_eq!(received.len(), filler.len() + sequence.len());
+        assert!(received.ends_with(sequence.as_bytes()));
+
+        // Nothing is written to a full pipe.
+        let (_reader, mut writer) = pipe();
+        while writer.write(&filler).is_ok() {}
+        let error = write_sequence(&mut writer, "z").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn send_and_save() {
+        use std::ffi::CStr;
+        use std::fs::File;
+        use std::io::Read;
+        use std::os::fd::FromRawFd;
+        use std::os::unix::fs::symlink;
+        use tempfile::tempdir;
+
+        let (mut master, mut slave) = (0, 0);
+        // SAFETY: both descriptors point to valid integers and the optional name, termios and
+        // winsize arguments may be null.
+        let result = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(result, 0);
+        // SAFETY: openpty succeeded, so slave is a terminal and ttyname returns a valid string,
+        // which nothing else in this test overwrites by calling ttyname again.
+        let slave_name = unsafe { CStr::from_ptr(libc::ttyname(slave)) };
+        // SAFETY: master is an open descriptor that nothing else owns.
+        let mut master = unsafe { File::from_raw_fd(master) };
+
+        // Mirror the layout of /dev/pts with the pseudo-terminal as the only terminal.
+        let pts_dir = tempdir().unwrap();
+        symlink(slave_name.to_str().unwrap(), pts_dir.path().join("0")).unwrap();
+        os::write_to_file(pts_dir.path().join("ptmx"), "").unwrap();
+
+        let sequences = Sequences::new(&theme());
+        assert_eq!(sequences.send(pts_dir.path()).unwrap(), 1);
+
+        let contents = sequences.contents();
+        let mut received = vec![0; contents.len()];
+        master.read_exact(&mut received).unwrap();
+        assert_eq!(String::from_utf8(received).unwrap(), contents);
+
+        // Nobody reads the terminal from here on, so its buffer fills up and it gets skipped.
+        let sends = (0..10_000)
+            .take_while(|_| sequences.send(pts_dir.path()).unwrap() == 1)
+            .count();
+        assert!(sends < 10_000);
+        // SAFETY: slave is open and not owned by anything that would close it again.
+        unsafe { libc::close(slave) };
+
+        let cache_dir = tempdir().unwrap();
+        let cache_dir = cache_dir.path().join("rswal");
+        sequences.save(&cache_dir).unwrap();
+        assert_eq!(
+            os::read_file(cache_dir.join(SEQUENCES_FILE)).unwrap(),
+            contents
+        );
+    }
+}
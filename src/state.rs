@@ -0,0 +1,100 @@
+use crate::os::{self, Path, PathBuf, ReadError, WriteError};
+use crate::renderer::{Serialize, Value};
+use crate::theme::Theme;
+use crate::yaml_parser::{self, Deserialize, ParseError, YamlParser};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+const STATE_FILE: &str = "state.yaml";
+
+#[derive(Error, Debug)]
+pub enum StateError {
+    #[error("read failed -> {0}")]
+    Read(#[from] ReadError),
+
+    #[error("parse failed -> {0}")]
+    Parse(#[from] ParseError),
+
+    #[error("write failed -> {0}")]
+    Write(#[from] WriteError),
+}
+
+/// The last applied theme along with everything needed to apply it again.
+#[derive(Deserialize, Serialize)]
+pub struct State {
+    pub theme: String,
+    pub colors: Theme,
+    pub variables: HashMap<String, Value>,
+    pub timestamp: u64,
+}
+
+impl State {
+    pub fn new(theme: &str, colors: Theme, variables: HashMap<String, Value>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        State {
+            theme: theme.to_string(),
+            colors,
+            variables,
+            timestamp,
+        }
+    }
+
+    pub fn file(cache_dir: &Path) -> PathBuf {
+        cache_dir.join(STATE_FILE)
+    }
+
+    pub fn load(cache_dir: &Path) -> Result<Self, StateError> {
+        let contents = os::read_file(Self::file(cache_dir))?;
+
+        Ok(YamlParser::parse(&contents)?)
+    }
+
+    pub fn save(&self, cache_dir: &Path) -> Result<(), StateError> {
+        let contents = yaml_parser::to_string(self)?;
+        os::create_dir(cache_dir)?;
+        os::write_to_file(Self::file(cache_dir), &contents)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    #[test]
+    fn save_and_load() {
+        use tempfile::tempdir;
+
+        let cache_dir = tempdir().unwrap();
+        let cache_dir_path = cache_dir.path();
+        assert!(matches!(
+            State::load(cache_dir_path),
+            Err(StateError::Read(ReadError::FileNotFound))
+        ));
+
+        let colors = Theme::from_palette(&[
+            Color::from_hex("#000000").unwrap(),
+            Color::from_hex("#ffffff").unwrap(),
+        ]);
+        let variables = HashMap::from([("alpha".to_string(), Value::from(0.5))]);
+        let state = State::new("monokai", colors, variables);
+        state.save(cache_dir_path).unwrap();
+
+        let loaded = State::load(cache_dir_path).unwrap();
+        assert_eq!(loaded.theme, "monokai");
+        assert_eq!(loaded.timestamp, state.timestamp);
+        assert_eq!(loaded.variables.get("alpha").unwrap(), 0.5);
+        assert_eq!(
+            loaded.colors.special.background,
+            state.colors.special.background
+        );
+        assert_eq!(loaded.colors.normal.red, state.colors.normal.red);
+    }
+}
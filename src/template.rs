@@ -1,5 +1,7 @@
-use crate::os::{self, Path, PathBuf, ReadError, WriteError};
-use crate::renderer::{RenderError, Renderer, Serialize};
+use crate::glob;
+use crate::os::{self, Path, PathBuf, ReadDirError, ReadError, Symlinks, WriteError, WriteOptions};
+use crate::renderer::{Context, RenderError, Renderer, Serialize};
+use std::thread;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -7,6 +9,9 @@ pub enum TemplateError {
     #[error("read failed -> {0}")]
     Read(#[from] ReadError),
 
+    #[error("parse failed -> {0}")]
+    Parse(RenderError),
+
     #[error("render failed -> {0}")]
     Render(#[from] RenderError),
 
@@ -14,37 +19,274 @@ pub enum TemplateError {
     Write(#[from] WriteError),
 }
 
+/// What rendering a template did to its target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderStatus {
+    Written,
+    Unchanged,
+    Failed,
+}
+
+impl RenderStatus {
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderStatus::Written => "written",
+            RenderStatus::Unchanged => "unchanged",
+            RenderStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Which files of a directory or glob source are used and which of those are copied as they are.
+/// See [`glob::matches_any`] for how the patterns match.
+#[derive(Debug, Default)]
+pub struct Filter<'a> {
+    /// Every file is included if empty.
+    pub include: &'a [String],
+    pub exclude: &'a [String],
+    pub copy: &'a [String],
+}
+
+impl Filter<'_> {
+    fn includes(&self, path: &str) -> bool {
+        (self.include.is_empty() || glob::matches_any(self.include, path))
+            && !glob::matches_any(self.exclude, path)
+    }
+}
+
 #[derive(Debug)]
-pub struct Template<'a> {
-    pub name: &'a str,
+pub struct Template {
+    pub name: String,
     source: PathBuf,
-    target: &'a Path,
+    target: PathBuf,
+    options: WriteOptions,
+    /// Copied to the target as it is instead of rendered.
+    verbatim: bool,
+    /// Rendered against this rather than the renderer's context.
+    context: Option<Context>,
 }
 
-impl<'a> Template<'a> {
-    pub fn new(source: &'a str, target: &'a Path, template_dir: &Path) -> Self {
+impl Template {
+    pub fn new(source: &str, target: &Path, template_dir: &Path, options: WriteOptions) -> Self {
         Template {
-            name: source,
+            name: source.to_string(),
             source: template_dir.join(source),
-            target,
+            target: target.to_path_buf(),
+            options,
+            verbatim: false,
+            context: None,
         }
     }
 
-    pub fn render<T: Serialize>(&self, renderer: &Renderer<T>) -> Result<(), TemplateError> {
+    /// The templates of a source, which is either a single file rendered to the target, or a
+    /// directory or glob whose files are rendered to their paths relative to it under the target
+    /// directory. Files that are not text are copied as they are, as are those the filter says to
+    /// copy, which a single file is matched against by its source.
+    pub fn expand(
+        source: &str,
+        target: &Path,
+        template_dir: &Path,
+        options: WriteOptions,
+        filter: &Filter,
+    ) -> Result<Vec<Self>, ReadDirError> {
+        let path = template_dir.join(source);
+        let base = match glob::is_glob(source) {
+            true => template_dir.join(glob::base(source)),
+            false if path.is_dir() => path,
+            false => {
+                let template = Template::new(source, target, template_dir, options);
+                return Ok(vec![Template {
+                    verbatim: is_verbatim(&path, source, filter),
+                    ..template
+                }]);
+            }
+        };
+        // The mirrored directories seldom exist beforehand.
+        let options = WriteOptions {
+            create_dirs: true,
+            ..options
+        };
+
+        let templates = os::walk_dir(&base)?
+            .into_iter()
+            .filter_map(|file| {
+                let name = file.strip_prefix(template_dir).ok()?.to_str()?;
+                let relative = file.strip_prefix(&base).ok()?.to_str()?;
+                if glob::is_glob(source) && !glob::matches(source, name) {
+                    return None;
+                }
+                if !filter.includes(relative) {
+                    return None;
+                }
+
+                let template = Template::new(name, &target.join(relative), template_dir, options);
+
+                Some(Template {
+                    verbatim: is_verbatim(&file, relative, filter),
+                    ..template
+                })
+            })
+            .collect();
+
+        Ok(templates)
+    }
+
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    pub fn set_context(&mut self, context: Context) {
+        self.context = Some(context);
+    }
+
+    /// Parses the source and registers it with the renderer, which it has to be before rendering.
+    pub fn compile<T: Serialize>(&self, renderer: &mut Renderer<T>) -> Result<(), TemplateError> {
+        if self.verbatim {
+            return Ok(());
+        }
+
         let contents = os::read_file(&self.source)?;
-        let rendered = renderer.render(&contents)?;
-        os::write_to_file(&self.target, &rendered)?;
 
-        Ok(())
+        renderer
+            .register(&self.name, &contents)
+            .map_err(TemplateError::Parse)
+    }
+
+    /// Renders to the target, leaving it alone if it already has the rendered contents so that
+    /// applications watching it are not reloaded needlessly.
+    pub fn render<T: Serialize>(
+        &self,
+        renderer: &Renderer<T>,
+    ) -> Result<RenderStatus, TemplateError> {
+        let rendered = self.render_to_bytes(renderer)?;
+        if self.is_current(&rendered) {
+            return Ok(RenderStatus::Unchanged);
+        }
+        os::write_with_options(&self.target, &rendered, self.options)?;
+
+        Ok(RenderStatus::Written)
+    }
+
+    /// Whether rendering would write to the target, which templates that fail to render do not.
+    pub fn needs_write<T: Serialize>(&self, renderer: &Renderer<T>) -> bool {
+        self.render_to_bytes(renderer)
+            .is_ok_and(|rendered| !self.is_current(&rendered))
+    }
+
+    /// Whether the target already is a file with the rendered contents and the configured mode.
+    fn is_current(&self, rendered: &[u8]) -> bool {
+        let replaces_symlink =
+            self.options.symlinks == Symlinks::Replace && self.target.is_symlink();
+        let changes_mode = self
+            .options
+            .mode
+            .is_some_and(|mode| os::mode(&self.target) != Some(mode));
+
+        !replaces_symlink
+            && !changes_mode
+            && os::read_bytes(&self.target).is_ok_and(|current| current == rendered)
+    }
+
+    /// The contents of the target, which for files copied as they are need not be text.
+    pub fn render_to_bytes<T: Serialize>(
+        &self,
+        renderer: &Renderer<T>,
+    ) -> Result<Vec<u8>, TemplateError> {
+        if self.verbatim {
+            return Ok(os::read_bytes(&self.source)?);
+        }
+        let rendered = match &self.context {
+            Some(context) => renderer.render_registered_with(&self.name, context)?,
+            None => renderer.render_registered(&self.name)?,
+        };
+
+        Ok(rendered.into_bytes())
     }
 }
 
+/// Whether the file is copied as it is, with `path` being what the filter's patterns match.
+fn is_verbatim(file: &Path, path: &str, filter: &Filter) -> bool {
+    glob::matches_any(filter.copy, path) || os::is_binary(file).unwrap_or_default()
+}
+
+/// A snippet that templates include with `{{> name}}`, named after its file without the extension.
+#[derive(Debug)]
+pub struct Partial {
+    pub name: String,
+    path: PathBuf,
+}
+
+impl Partial {
+    /// The partials in the directory, which may not exist.
+    pub fn list(partial_dir: &Path) -> Result<Vec<Self>, ReadDirError> {
+        let files = match os::read_dir(partial_dir) {
+            Ok(files) => files,
+            Err(ReadDirError::DirectoryDoesNotExist) => return Ok(vec![]),
+            Err(err) => return Err(err),
+        };
+        let mut partials = files
+            .into_iter()
+            .filter(|file| file.is_file())
+            .filter_map(|path| {
+                let name = path.file_stem()?.to_str()?.to_string();
+                Some(Partial { name, path })
+            })
+            .collect::<Vec<Partial>>();
+        partials.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(partials)
+    }
+
+    pub fn compile<T: Serialize>(&self, renderer: &mut Renderer<T>) -> Result<(), TemplateError> {
+        renderer
+            .register_partial(&self.name, &self.path)
+            .map_err(TemplateError::Parse)
+    }
+}
+
+/// Renders the templates concurrently, spread over the available cores, and returns their results
+/// in the same order as the templates.
+pub fn render_all<T: Serialize + Sync>(
+    templates: &[Template],
+    renderer: &Renderer<T>,
+) -> Vec<Result<RenderStatus, TemplateError>> {
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let chunk_size = templates.len().div_ceil(threads).max(1);
+
+    thread::scope(|scope| {
+        let handles = templates
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(|| {
+                    chunk
+                        .iter()
+                        .map(|template| template.render(renderer))
+                        .collect::<Vec<Result<RenderStatus, TemplateError>>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("rendering thread panicked"))
+            .collect()
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::renderer::context;
     use tempfile::tempdir;
 
+    fn rendered<T: Serialize>(template: &Template, renderer: &Renderer<T>) -> String {
+        String::from_utf8(template.render_to_bytes(renderer).unwrap()).unwrap()
+    }
+
     #[test]
     fn render() {
         let dir = tempdir().unwrap();
@@ -54,15 +296,235 @@ mod tests {
         let source_path = dir_path.join(source);
         os::write_to_file(&source_path, "name: {{name}}").unwrap();
 
-        let target = dir_path.join("target");
-        let template = Template::new(source, &target, &dir_path);
+        let target = dir_path.join("sub/target");
+        let options = WriteOptions {
+            create_dirs: true,
+            ..Default::default()
+        };
+        let mut template = Template::new(source, &target, dir_path, options);
 
         let context = context!({
             "name": "John"
         });
-        let renderer = Renderer::new(&context);
-        template.render(&renderer).unwrap();
+        let mut renderer = Renderer::new(&context);
+        assert!(template.render_to_bytes(&renderer).is_err());
+        template.compile(&mut renderer).unwrap();
+        assert_eq!(rendered(&template, &renderer), "name: John");
+        assert!(!target.exists());
+        assert!(template.needs_write(&renderer));
 
+        assert_eq!(template.render(&renderer).unwrap(), RenderStatus::Written);
         assert_eq!(os::read_file(&target).unwrap(), "name: John");
+
+        let modified = os::modified(&target);
+        assert!(!template.needs_write(&renderer));
+        assert_eq!(template.render(&renderer).unwrap(), RenderStatus::Unchanged);
+        assert_eq!(os::modified(&target), modified);
+
+        template.options.mode = Some(0o600);
+        assert!(template.needs_write(&renderer));
+        assert_eq!(template.render(&renderer).unwrap(), RenderStatus::Written);
+        assert_eq!(os::mode(&target), Some(0o600));
+        assert_eq!(template.render(&renderer).unwrap(), RenderStatus::Unchanged);
+
+        template.set_context(context!({ "name": "Jane" }));
+        assert_eq!(rendered(&template, &renderer), "name: Jane");
+
+        let missing = Template::new("missing", &target, dir_path, options);
+        assert_eq!(
+            missing.compile(&mut renderer).unwrap_err().to_string(),
+            "read failed -> file not found"
+        );
+    }
+
+    #[test]
+    fn expand() {
+        let dir = tempdir().unwrap();
+        let template_dir = dir.path().join("templates");
+        let target = dir.path().join("gtk-3.0");
+        let files = [
+            ("gtk/gtk.css", "{{color}}".as_bytes()),
+            ("gtk/dark/gtk.css", "{{color}}".as_bytes()),
+            ("gtk/README.md", "{{".as_bytes()),
+            ("gtk/assets/check.png", &[0x89, 0x50, 0x00, 0xff]),
+            ("gtk/assets/icon.svg", "{{raw}}".as_bytes()),
+            ("gtk/latin1.txt", b"caf\xe9 {{\n"),
+        ];
+        for (file, contents) in files {
+            os::write_with_options(
+                template_dir.join(file),
+                contents,
+                WriteOptions {
+                    create_dirs: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        }
+
+        let (exclude, copy) = (["*.md".to_string()], ["assets/*.svg".to_string()]);
+        let filter = Filter {
+            exclude: &exclude,
+            copy: &copy,
+            ..Default::default()
+        };
+        let expand = |source: &str| {
+            Template::expand(
+                source,
+                &target,
+                &template_dir,
+                WriteOptions::default(),
+                &filter,
+            )
+            .unwrap()
+        };
+
+        let templates = expand("gtk");
+        let names = templates
+            .iter()
+            .map(|template| template.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            names,
+            [
+                "gtk/assets/check.png",
+                "gtk/assets/icon.svg",
+                "gtk/dark/gtk.css",
+                "gtk/gtk.css",
+                "gtk/latin1.txt"
+            ]
+        );
+        assert_eq!(templates[2].target(), target.join("dark/gtk.css"));
+        assert_eq!(expand("gtk/**").len(), 5);
+        assert_eq!(
+            expand("gtk/*/*.css")[0].target(),
+            target.join("dark/gtk.css")
+        );
+        assert_eq!(expand("gtk/gtk.css")[0].target(), target);
+
+        // Single files are copied as they are too when the filter says so or they are binary.
+        assert!(!expand("gtk/assets/icon.svg")[0].verbatim);
+        assert!(expand("gtk/assets/check.png")[0].verbatim);
+        let copy = ["*.svg".to_string()];
+        let svg = Template::expand(
+            "gtk/assets/icon.svg",
+            &target,
+            &template_dir,
+            WriteOptions::default(),
+            &Filter {
+                copy: &copy,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(svg[0].verbatim);
+
+        let context = context!({ "color": "#000000" });
+        let mut renderer = Renderer::new(&context);
+        for template in &templates {
+            template.compile(&mut renderer).unwrap();
+            assert_eq!(template.render(&renderer).unwrap(), RenderStatus::Written);
+        }
+        assert_eq!(
+            os::read_bytes(target.join("assets/check.png")).unwrap(),
+            [0x89, 0x50, 0x00, 0xff]
+        );
+        assert_eq!(
+            os::read_file(target.join("assets/icon.svg")).unwrap(),
+            "{{raw}}"
+        );
+        assert_eq!(os::read_file(target.join("gtk.css")).unwrap(), "#000000");
+        // Sources that are not UTF-8 cannot be templates, so they are copied as well.
+        assert_eq!(
+            os::read_bytes(target.join("latin1.txt")).unwrap(),
+            b"caf\xe9 {{\n"
+        );
+    }
+
+    #[test]
+    fn partials() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path();
+        let partial_dir = dir_path.join("partials");
+        assert!(Partial::list(&partial_dir).unwrap().is_empty());
+
+        os::create_dir(&partial_dir).unwrap();
+        os::write_to_file(partial_dir.join("palette.hbs"), "color: {{color}}").unwrap();
+        os::write_to_file(dir_path.join("source"), "{{> palette}}").unwrap();
+        let partials = Partial::list(&partial_dir).unwrap();
+        assert_eq!(partials.len(), 1);
+        assert_eq!(partials[0].name, "palette");
+
+        let target = dir_path.join("target");
+        let template = Template::new("source", &target, dir_path, WriteOptions::default());
+        let context = context!({ "color": "#000000" });
+        let mut renderer = Renderer::new(&context);
+        partials[0].compile(&mut renderer).unwrap();
+        template.compile(&mut renderer).unwrap();
+        assert_eq!(rendered(&template, &renderer), "color: #000000");
+    }
+
+    #[test]
+    fn render_all() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path();
+
+        let count = 100;
+        let names = (0..count).map(|i| format!("{i}")).collect::<Vec<String>>();
+        // Enough templates and helper calls to keep every thread busy.
+        let contents = "{{lighten color 0.1}} {{name}}\n".repeat(20);
+        for name in &names {
+            os::write_to_file(dir_path.join(name), &contents).unwrap();
+        }
+        os::write_to_file(dir_path.join("broken"), "{{missing}}").unwrap();
+
+        let options = WriteOptions {
+            create_dirs: true,
+            ..Default::default()
+        };
+        let templates = |out: &str| {
+            let mut templates = names
+                .iter()
+                .map(|name| Template::new(name, &dir_path.join(out).join(name), dir_path, options))
+                .collect::<Vec<Template>>();
+            let broken_target = dir_path.join(out).join("broken");
+            templates.insert(
+                count / 2,
+                Template::new("broken", &broken_target, dir_path, options),
+            );
+
+            templates
+        };
+
+        let context = context!({
+            "name": "John",
+            "color": "#000000",
+        });
+        let mut renderer = Renderer::new(&context);
+        let (sequential, parallel) = (templates("sequential"), templates("parallel"));
+        for template in &parallel {
+            template.compile(&mut renderer).unwrap();
+        }
+
+        let expected = sequential
+            .iter()
+            .map(|template| template.render(&renderer))
+            .collect::<Vec<Result<RenderStatus, TemplateError>>>();
+        let results = super::render_all(&parallel, &renderer);
+
+        assert_eq!(results.len(), count + 1);
+        for (index, (result, expected)) in results.iter().zip(&expected).enumerate() {
+            match index == count / 2 {
+                true => assert!(matches!(result, Err(TemplateError::Render(_)))),
+                false => assert_eq!(*result.as_ref().unwrap(), RenderStatus::Written),
+            }
+            assert_eq!(result.is_ok(), expected.is_ok());
+        }
+        let expected = "#1a1a1a John\n".repeat(20);
+        assert!(parallel
+            .iter()
+            .chain(&sequential)
+            .filter(|template| template.name != "broken")
+            .all(|template| os::read_file(template.target()).unwrap() == expected));
     }
 }
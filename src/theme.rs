@@ -1,34 +1,92 @@
-use crate::os::{self, Path, ReadError};
+use crate::color::Color;
+use crate::import::{self, Format, ImportError};
+use crate::os::{self, Path, PathBuf, ReadDirError, ReadError};
 use crate::renderer::Serialize;
+use crate::wallpaper::{Wallpaper, WallpaperError};
 use crate::yaml_parser::{Deserialize, ParseError, YamlParser};
+use clap::ValueEnum;
+use std::collections::BTreeMap;
 use thiserror::Error;
 
+const PALETTE_SIZE: usize = 16;
+
 #[derive(Error, Debug)]
 pub enum ThemeError {
+    #[error("not found")]
+    NotFound,
+
+    #[error("several files match: {0}")]
+    Ambiguous(String),
+
     #[error("read failed -> {0}")]
     Read(#[from] ReadError),
 
+    #[error("could not read theme directory -> {0}")]
+    ReadDir(#[from] ReadDirError),
+
     #[error("parse failed -> {0}")]
     Parse(#[from] ParseError),
+
+    #[error("import failed -> {0}")]
+    Import(#[from] ImportError),
+
+    #[error("image failed -> {0}")]
+    Image(#[from] WallpaperError),
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct SpecialColors {
-    pub background: String,
-    pub foreground: String,
-    pub cursor: String,
+    pub background: Color,
+    pub foreground: Color,
+    pub cursor: Color,
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct AnsiColors {
-    pub black: String,
-    pub red: String,
-    pub green: String,
-    pub yellow: String,
-    pub blue: String,
-    pub magenta: String,
-    pub cyan: String,
-    pub white: String,
+    pub black: Color,
+    pub red: Color,
+    pub green: Color,
+    pub yellow: Color,
+    pub blue: Color,
+    pub magenta: Color,
+    pub cyan: Color,
+    pub white: Color,
+}
+
+impl AnsiColors {
+    /// The colors in the order of their ANSI codes.
+    pub fn colors(&self) -> [Color; 8] {
+        [
+            self.black,
+            self.red,
+            self.green,
+            self.yellow,
+            self.blue,
+            self.magenta,
+            self.cyan,
+            self.white,
+        ]
+    }
+
+    fn named_mut(&mut self) -> [(&'static str, &mut Color); 8] {
+        [
+            ("black", &mut self.black),
+            ("red", &mut self.red),
+            ("green", &mut self.green),
+            ("yellow", &mut self.yellow),
+            ("blue", &mut self.blue),
+            ("magenta", &mut self.magenta),
+            ("cyan", &mut self.cyan),
+            ("white", &mut self.white),
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Variant {
+    Light,
+    Dark,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -36,22 +94,267 @@ pub struct Theme {
     pub special: SpecialColors,
     pub normal: AnsiColors,
     pub bright: AnsiColors,
+
+    /// The raw `baseXX` slots of themes imported from base16 and base24 schemes.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub base: BTreeMap<String, Color>,
 }
 
 impl Theme {
     pub fn new(name: &str, theme_dir: &Path) -> Result<Self, ThemeError> {
-        let file = Path::new(theme_dir).join(Path::new(name).with_extension("yaml"));
+        let (file, format) = Self::find(name, theme_dir)?;
         let contents = os::read_file(&file)?;
 
-        Ok(Self::try_from(contents.as_str())?)
+        match format.detect(&contents) {
+            Format::Yaml => Ok(Self::try_from(contents.as_str())?),
+            format => Ok(import::import(format, &contents)?),
+        }
     }
+
+    /// The file a theme is loaded from.
+    pub fn file(name: &str, theme_dir: &Path) -> Option<PathBuf> {
+        Self::find(name, theme_dir).ok().map(|(file, _)| file)
+    }
+
+    /// Lists the names of the themes in `theme_dir` and its subdirectories, in every supported
+    /// format. Themes in subdirectories are named like `dark/nord`, which `find` accepts.
+    pub fn list(theme_dir: &Path) -> Result<Vec<String>, ReadDirError> {
+        let mut themes = os::walk_dir(theme_dir)?
+            .iter()
+            .filter_map(|file| {
+                let (name, _) = theme_file(file)?;
+                let dir = file.parent()?.strip_prefix(theme_dir).ok()?;
+                dir.join(name).to_str().map(str::to_string)
+            })
+            .collect::<Vec<String>>();
+        themes.sort();
+        themes.dedup();
+
+        Ok(themes)
+    }
+
+    /// The file of the theme, which names like `dark/nord` look for in a subdirectory. A name
+    /// with the suffix of a known format, like `nord.yaml`, picks that file if it exists and is
+    /// otherwise looked up without the suffix. A native YAML theme takes precedence over files in
+    /// other formats with the same name, which would otherwise be ambiguous.
+    fn find(name: &str, theme_dir: &Path) -> Result<(PathBuf, Format), ThemeError> {
+        let path = theme_dir.join(name);
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name().and_then(|n| n.to_str()))
+        else {
+            return Err(ThemeError::NotFound);
+        };
+        let name = match theme_file(&path) {
+            Some((_, format)) if path.is_file() => return Ok((path.clone(), format)),
+            Some((name, _)) => name,
+            None => name,
+        };
+        let mut files = match os::read_dir(dir) {
+            Ok(files) => files,
+            Err(ReadDirError::DirectoryDoesNotExist) if dir != theme_dir => {
+                return Err(ThemeError::NotFound)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        files.sort();
+
+        let matches = files
+            .into_iter()
+            .filter_map(|file| {
+                let (theme_name, format) = theme_file(&file)?;
+                (theme_name == name).then(|| (file.clone(), format))
+            })
+            .collect::<Vec<(PathBuf, Format)>>();
+        if let Some(native) = matches.iter().find(|(_, format)| *format == Format::Yaml) {
+            return Ok(native.clone());
+        }
+
+        match matches.as_slice() {
+            [] => Err(ThemeError::NotFound),
+            [found] => Ok(found.clone()),
+            _ => Err(ThemeError::Ambiguous(
+                matches
+                    .iter()
+                    .map(|(file, _)| file.display().to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            )),
+        }
+    }
+
+    pub fn from_image(image: &Path) -> Result<Self, ThemeError> {
+        let wallpaper = Wallpaper::new(image)?;
+
+        Ok(Self::from_palette(&wallpaper.palette(PALETTE_SIZE)))
+    }
+
+    /// Builds a theme out of the dominant colors of a palette. The darkest and lightest colors
+    /// become the background and the foreground, the remaining ones are assigned to the ANSI
+    /// slots with the closest hue.
+    pub fn from_palette(palette: &[Color]) -> Self {
+        let mut colors = palette.to_vec();
+        if colors.is_empty() {
+            colors.push(Color::new(0, 0, 0));
+        }
+        colors.sort_by(|a, b| a.lightness().total_cmp(&b.lightness()));
+
+        let darkest = colors[0];
+        let lightest = colors[colors.len() - 1];
+        let accents = match colors.len() {
+            0..=2 => colors.clone(),
+            _ => colors[1..colors.len() - 1].to_vec(),
+        };
+
+        let background = darkest.darken(0.6);
+        let foreground = lightest.lighten(0.6);
+        let accent = |hue: f32| {
+            accents
+                .iter()
+                .min_by(|a, b| {
+                    hue_distance(a.hue(), hue)
+                        .total_cmp(&hue_distance(b.hue(), hue))
+                        .then(b.saturation().total_cmp(&a.saturation()))
+                })
+                .copied()
+                .unwrap_or(foreground)
+        };
+
+        let normal = AnsiColors {
+            black: background.lighten(0.1),
+            red: accent(0.0),
+            green: accent(120.0),
+            yellow: accent(60.0),
+            blue: accent(240.0),
+            magenta: accent(300.0),
+            cyan: accent(180.0),
+            white: foreground.darken(0.2),
+        };
+        let bright = AnsiColors {
+            black: background.lighten(0.3),
+            red: accent(0.0).lighten(0.2),
+            green: accent(120.0).lighten(0.2),
+            yellow: accent(60.0).lighten(0.2),
+            blue: accent(240.0).lighten(0.2),
+            magenta: accent(300.0).lighten(0.2),
+            cyan: accent(180.0).lighten(0.2),
+            white: foreground,
+        };
+
+        Theme {
+            special: SpecialColors {
+                background,
+                foreground,
+                cursor: foreground,
+            },
+            normal,
+            bright,
+            base: BTreeMap::new(),
+        }
+    }
+
+    /// The base16 slots of the theme. Themes which were not imported from a base16 scheme have
+    /// only 16 colors, so the slots without an ANSI counterpart are approximated.
+    pub fn base16(&self) -> BTreeMap<String, Color> {
+        if !self.base.is_empty() {
+            return self.base.clone();
+        }
+
+        let slots = [
+            self.special.background,
+            self.normal.black,
+            self.bright.black.darken(0.3),
+            self.bright.black,
+            self.special.foreground.darken(0.2),
+            self.special.foreground,
+            self.normal.white,
+            self.bright.white,
+            self.normal.red,
+            self.normal.red.shift_hue(30.0),
+            self.normal.yellow,
+            self.normal.green,
+            self.normal.cyan,
+            self.normal.blue,
+            self.normal.magenta,
+            self.normal.red.darken(0.4),
+        ];
+
+        slots
+            .into_iter()
+            .enumerate()
+            .map(|(slot, color)| (format!("base{slot:02X}"), color))
+            .collect()
+    }
+
+    pub fn is_dark(&self) -> bool {
+        self.special.background.is_dark()
+    }
+
+    /// Derives the light or dark variant of the theme by inverting the lightness of every color.
+    /// Themes which already have the requested polarity are returned as they are.
+    pub fn variant(mut self, variant: Variant) -> Self {
+        if self.is_dark() == (variant == Variant::Dark) {
+            return self;
+        }
+
+        self.special.background = self.special.background.invert_lightness();
+        self.special.cursor = self.special.cursor.invert_lightness();
+        for (_, color) in self.text_colors_mut() {
+            *color = color.invert_lightness();
+        }
+        for color in self.base.values_mut() {
+            *color = color.invert_lightness();
+        }
+
+        self
+    }
+
+    /// The theme as exposed to templates, along with the properties derived from it.
+    pub fn context(&self) -> ThemeContext<'_> {
+        ThemeContext {
+            theme: self,
+            is_dark: self.is_dark(),
+        }
+    }
+
+    /// The colors which are drawn on top of the background, keyed by their path in the theme.
+    pub fn text_colors_mut(&mut self) -> Vec<(String, &mut Color)> {
+        let mut colors = vec![(
+            "special.foreground".to_string(),
+            &mut self.special.foreground,
+        )];
+        for (group, ansi) in [("normal", &mut self.normal), ("bright", &mut self.bright)] {
+            colors.extend(
+                ansi.named_mut()
+                    .into_iter()
+                    .map(|(name, color)| (format!("{group}.{name}"), color)),
+            );
+        }
+
+        colors
+    }
+}
+
+#[derive(Serialize)]
+pub struct ThemeContext<'a> {
+    #[serde(flatten)]
+    theme: &'a Theme,
+    is_dark: bool,
+}
+
+fn theme_file(file: &Path) -> Option<(&str, Format)> {
+    Format::from_file_name(file.file_name()?.to_str()?)
+}
+
+fn hue_distance(a: f32, b: f32) -> f32 {
+    let distance = (a - b).abs() % 360.0;
+
+    distance.min(360.0 - distance)
 }
 
 impl TryFrom<&str> for Theme {
     type Error = ParseError;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        YamlParser::parse(value)
+        YamlParser::parse_with_path(value)
     }
 }
 
@@ -100,26 +403,187 @@ bright:
 
         let theme = Theme::new(theme_name, theme_dir_path).unwrap();
 
-        assert_eq!(theme.special.background, "#222222");
-        assert_eq!(theme.special.foreground, "#f7f1ff");
-        assert_eq!(theme.special.cursor, "#f7f1ff");
-
-        assert_eq!(theme.normal.black, "#363537");
-        assert_eq!(theme.normal.blue, "#948ae3");
-        assert_eq!(theme.normal.cyan, "#5ad4e6");
-        assert_eq!(theme.normal.green, "#7bd88f");
-        assert_eq!(theme.normal.magenta, "#fd9353");
-        assert_eq!(theme.normal.red, "#fc618d");
-        assert_eq!(theme.normal.white, "#bab6c0");
-        assert_eq!(theme.normal.yellow, "#fce566");
-
-        assert_eq!(theme.bright.black, "#69676c");
-        assert_eq!(theme.bright.blue, "#948ae3");
-        assert_eq!(theme.bright.cyan, "#5ad4e6");
-        assert_eq!(theme.bright.green, "#7bd88f");
-        assert_eq!(theme.bright.magenta, "#fd9353");
-        assert_eq!(theme.bright.red, "#fc618d");
-        assert_eq!(theme.bright.white, "#f7f1ff");
-        assert_eq!(theme.bright.yellow, "#fce566");
+        assert_eq!(theme.special.background.to_hex(), "#222222");
+        assert_eq!(theme.special.foreground.to_hex(), "#f7f1ff");
+        assert_eq!(theme.special.cursor.to_hex(), "#f7f1ff");
+
+        assert_eq!(theme.normal.black.to_hex(), "#363537");
+        assert_eq!(theme.normal.blue.to_hex(), "#948ae3");
+        assert_eq!(theme.normal.cyan.to_hex(), "#5ad4e6");
+        assert_eq!(theme.normal.green.to_hex(), "#7bd88f");
+        assert_eq!(theme.normal.magenta.to_hex(), "#fd9353");
+        assert_eq!(theme.normal.red.to_hex(), "#fc618d");
+        assert_eq!(theme.normal.white.to_hex(), "#bab6c0");
+        assert_eq!(theme.normal.yellow.to_hex(), "#fce566");
+
+        assert_eq!(theme.bright.black.to_hex(), "#69676c");
+        assert_eq!(theme.bright.blue.to_hex(), "#948ae3");
+        assert_eq!(theme.bright.cyan.to_hex(), "#5ad4e6");
+        assert_eq!(theme.bright.green.to_hex(), "#7bd88f");
+        assert_eq!(theme.bright.magenta.to_hex(), "#fd9353");
+        assert_eq!(theme.bright.red.to_hex(), "#fc618d");
+        assert_eq!(theme.bright.white.to_hex(), "#f7f1ff");
+        assert_eq!(theme.bright.yellow.to_hex(), "#fce566");
+        assert!(theme.base.is_empty());
+
+        os::write_to_file(
+            &theme_file,
+            "
+special:
+  background: '#222222'
+  foreground: '#f7f1ff'
+  cursor: '#12345'
+",
+        )
+        .unwrap();
+        let err = Theme::new(theme_name, theme_dir_path).err().unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("parse failed -> special.cursor: invalid color '#12345'"));
+    }
+
+    #[test]
+    fn base16() {
+        use tempfile::tempdir;
+
+        let theme_dir = tempdir().unwrap();
+        let theme_dir_path = theme_dir.path();
+        let slots = (0..16)
+            .map(|slot| format!("base{slot:02X}: '{:02x}{:02x}{:02x}'", slot, slot, slot))
+            .collect::<Vec<String>>()
+            .join("\n");
+        os::write_to_file(theme_dir_path.join("ocean.base16.yaml"), &slots).unwrap();
+        os::write_to_file(theme_dir_path.join("detected.yaml"), &slots).unwrap();
+        os::write_to_file(theme_dir_path.join("notes.txt"), "").unwrap();
+
+        assert_eq!(
+            Theme::list(theme_dir_path).unwrap(),
+            vec!["detected", "ocean"]
+        );
+
+        let theme = Theme::new("ocean", theme_dir_path).unwrap();
+        assert_eq!(theme.special.background.to_hex(), "#000000");
+        assert_eq!(theme.base.get("base0F").unwrap().to_hex(), "#0f0f0f");
+
+        let theme = Theme::new("detected", theme_dir_path).unwrap();
+        assert_eq!(theme.normal.red.to_hex(), "#080808");
+
+        assert!(matches!(
+            Theme::new("missing", theme_dir_path),
+            Err(ThemeError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn same_name() {
+        use tempfile::tempdir;
+
+        let theme_dir = tempdir().unwrap();
+        let theme_dir_path = theme_dir.path();
+        for file in ["foo.toml", "foo.yaml", "bar.toml", "bar.conf"] {
+            os::write_to_file(theme_dir_path.join(file), "").unwrap();
+        }
+
+        assert_eq!(
+            Theme::file("foo", theme_dir_path),
+            Some(theme_dir_path.join("foo.yaml"))
+        );
+        assert!(matches!(
+            Theme::new("bar", theme_dir_path),
+            Err(ThemeError::Ambiguous(_))
+        ));
+    }
+
+    #[test]
+    fn nested() {
+        use tempfile::tempdir;
+
+        let theme_dir = tempdir().unwrap();
+        let theme_dir_path = theme_dir.path();
+        let theme = Theme::from_palette(&[Color::new(0x10, 0x10, 0x10)]);
+        let file = theme_dir_path.join("dark/nord.yaml");
+        os::create_dir(file.parent().unwrap()).unwrap();
+        os::write_to_file(&file, crate::yaml_parser::to_string(&theme).unwrap()).unwrap();
+
+        assert_eq!(Theme::file("dark/nord", theme_dir_path), Some(file));
+        let loaded = Theme::new("dark/nord", theme_dir_path).unwrap();
+        assert_eq!(
+            loaded.special.background.to_hex(),
+            theme.special.background.to_hex()
+        );
+        assert!(matches!(
+            Theme::new("light/nord", theme_dir_path),
+            Err(ThemeError::NotFound)
+        ));
+
+        // Names may carry the suffix of their format.
+        let file = theme_dir_path.join("dark/nord.yaml");
+        assert_eq!(
+            Theme::file("dark/nord.yaml", theme_dir_path),
+            Some(file.clone())
+        );
+        assert_eq!(Theme::file("dark/nord.yml", theme_dir_path), Some(file));
+
+        os::write_to_file(theme_dir_path.join("mono.yaml"), "").unwrap();
+        assert_eq!(
+            Theme::list(theme_dir_path).unwrap(),
+            vec!["dark/nord", "mono"]
+        );
+    }
+
+    #[test]
+    fn variant() {
+        use crate::renderer::{context, Renderer};
+        use crate::yaml_parser;
+
+        let theme = Theme::from_palette(&[
+            Color::from_hex("#101418").unwrap(),
+            Color::from_hex("#d8dee9").unwrap(),
+            Color::from_hex("#bf616a").unwrap(),
+            Color::from_hex("#5e81ac").unwrap(),
+        ]);
+        assert!(theme.is_dark());
+        let red = theme.normal.red;
+
+        let light = theme.variant(Variant::Light);
+        assert!(!light.is_dark());
+        assert!(light.special.foreground.is_dark());
+        // The hue is kept in OkLCh, which drifts slightly in HSL.
+        assert!((light.normal.red.hue() - red.hue()).abs() < 5.0);
+
+        let unchanged = yaml_parser::to_string(&light).unwrap();
+        let light = light.variant(Variant::Light);
+        assert_eq!(yaml_parser::to_string(&light).unwrap(), unchanged);
+
+        let context = context!({ "colors": light.context() });
+        let renderer = Renderer::new(&context);
+        assert_eq!(
+            renderer
+                .render(
+                    "{{#if colors.is_dark}}dark{{else}}light{{/if}} {{colors.special.background}}"
+                )
+                .unwrap(),
+            format!("light {}", light.special.background)
+        );
+    }
+
+    #[test]
+    fn from_palette() {
+        let palette = [
+            Color::from_hex("#101010").unwrap(),
+            Color::from_hex("#e0e0e0").unwrap(),
+            Color::from_hex("#cc3333").unwrap(),
+            Color::from_hex("#33cc33").unwrap(),
+            Color::from_hex("#3333cc").unwrap(),
+        ];
+        let theme = Theme::from_palette(&palette);
+
+        assert_eq!(theme.special.background.to_hex(), "#060606");
+        assert_eq!(theme.special.foreground.to_hex(), "#f3f3f3");
+        assert_eq!(theme.special.cursor, theme.special.foreground);
+        assert_eq!(theme.normal.red.to_hex(), "#cc3333");
+        assert_eq!(theme.normal.green.to_hex(), "#33cc33");
+        assert_eq!(theme.normal.blue.to_hex(), "#3333cc");
+        assert_eq!(theme.bright.white, theme.special.foreground);
     }
 }
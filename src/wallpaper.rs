@@ -0,0 +1,164 @@
+use crate::color::Color;
+use crate::os::Path;
+use image::{ImageError, ImageReader};
+use std::io::ErrorKind as IoErrorKind;
+use thiserror::Error;
+
+// Wallpapers are downscaled before quantization, the palette barely changes but the work does.
+const THUMBNAIL_SIZE: u32 = 256;
+
+#[derive(Error, Debug)]
+pub enum WallpaperError {
+    #[error("file not found")]
+    FileNotFound,
+
+    #[error("permission denied")]
+    PermissionDenied,
+
+    #[error("could not decode image: {0}")]
+    Decode(ImageError),
+}
+
+impl From<ImageError> for WallpaperError {
+    fn from(error: ImageError) -> Self {
+        match error {
+            ImageError::IoError(error) => match error.kind() {
+                IoErrorKind::NotFound => WallpaperError::FileNotFound,
+                IoErrorKind::PermissionDenied => WallpaperError::PermissionDenied,
+                _ => WallpaperError::Decode(ImageError::IoError(error)),
+            },
+            error => WallpaperError::Decode(error),
+        }
+    }
+}
+
+pub struct Wallpaper {
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Wallpaper {
+    pub fn new(path: &Path) -> Result<Self, WallpaperError> {
+        let mut image = ImageReader::open(path)
+            .map_err(ImageError::IoError)?
+            .with_guessed_format()
+            .map_err(ImageError::IoError)?
+            .decode()?;
+        if image.width() > THUMBNAIL_SIZE || image.height() > THUMBNAIL_SIZE {
+            image = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+        }
+        let image = image.to_rgb8();
+
+        Ok(Wallpaper {
+            pixels: image.pixels().map(|pixel| pixel.0).collect(),
+        })
+    }
+
+    /// Quantizes the wallpaper into at most `size` colors using median cut, ordered from the most
+    /// to the least dominant one.
+    pub fn palette(&self, size: usize) -> Vec<Color> {
+        let mut buckets = vec![self.pixels.clone()];
+
+        while buckets.len() < size {
+            let widest = buckets
+                .iter()
+                .enumerate()
+                .filter(|(_, bucket)| bucket.len() > 1)
+                .map(|(index, bucket)| (index, widest_channel(bucket)))
+                .max_by_key(|(_, (_, range))| *range);
+
+            let Some((index, (channel, range))) = widest else {
+                break;
+            };
+            if range == 0 {
+                break;
+            }
+
+            let mut bucket = buckets.swap_remove(index);
+            bucket.sort_unstable_by_key(|pixel| pixel[channel]);
+            let upper = bucket.split_off(bucket.len() / 2);
+            buckets.push(bucket);
+            buckets.push(upper);
+        }
+
+        // Splitting at the median can leave identical colors in several buckets, merge them back.
+        let mut colors: Vec<([u8; 3], usize)> = Vec::new();
+        buckets
+            .iter()
+            .filter(|bucket| !bucket.is_empty())
+            .for_each(|bucket| {
+                let color = average(bucket);
+                match colors.iter_mut().find(|(existing, _)| *existing == color) {
+                    Some((_, count)) => *count += bucket.len(),
+                    None => colors.push((color, bucket.len())),
+                }
+            });
+
+        colors.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        colors
+            .into_iter()
+            .map(|([red, green, blue], _)| Color::new(red, green, blue))
+            .collect()
+    }
+}
+
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let (min, max) = pixels.iter().fold((u8::MAX, u8::MIN), |(min, max), pixel| {
+                (min.min(pixel[channel]), max.max(pixel[channel]))
+            });
+
+            (channel, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or_default()
+}
+
+fn average(pixels: &[[u8; 3]]) -> [u8; 3] {
+    let sum = pixels.iter().fold([0u64; 3], |mut sum, pixel| {
+        sum.iter_mut()
+            .zip(pixel)
+            .for_each(|(sum, value)| *sum += *value as u64);
+        sum
+    });
+    let count = pixels.len() as u64;
+
+    sum.map(|sum| (sum / count) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette() {
+        use image::{Rgb, RgbImage};
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("wallpaper.png");
+
+        // Three quarters red, one quarter blue.
+        let image = RgbImage::from_fn(64, 64, |x, _| {
+            if x < 48 {
+                Rgb([255, 0, 0])
+            } else {
+                Rgb([0, 0, 255])
+            }
+        });
+        image.save(&file).unwrap();
+
+        let wallpaper = Wallpaper::new(&file).unwrap();
+        let palette = wallpaper
+            .palette(16)
+            .into_iter()
+            .map(Color::to_hex)
+            .collect::<Vec<String>>();
+        assert_eq!(palette, vec!["#ff0000", "#0000ff"]);
+
+        assert!(matches!(
+            Wallpaper::new(&dir.path().join("missing.png")),
+            Err(WallpaperError::FileNotFound)
+        ));
+    }
+}
@@ -0,0 +1,89 @@
+use crate::os::{self, PathBuf};
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Watches files and directories by polling their modification times, which also catches editors
+/// that save by replacing files.
+pub struct Watcher {
+    paths: Vec<PathBuf>,
+    files: BTreeMap<PathBuf, SystemTime>,
+}
+
+impl Watcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let files = scan(&paths);
+
+        Watcher { paths, files }
+    }
+
+    /// The files which were modified, created or removed since the last call.
+    pub fn changes(&mut self) -> Vec<PathBuf> {
+        let files = scan(&self.paths);
+        let mut changes = files
+            .iter()
+            .filter(|(file, modified)| self.files.get(*file) != Some(modified))
+            .map(|(file, _)| file.clone())
+            .collect::<Vec<PathBuf>>();
+        changes.extend(
+            self.files
+                .keys()
+                .filter(|file| !files.contains_key(*file))
+                .cloned(),
+        );
+        self.files = files;
+
+        changes
+    }
+
+    /// Blocks until something changes.
+    pub fn wait(&mut self, interval: Duration) -> Vec<PathBuf> {
+        loop {
+            thread::sleep(interval);
+
+            let changes = self.changes();
+            if !changes.is_empty() {
+                return changes;
+            }
+        }
+    }
+}
+
+fn scan(paths: &[PathBuf]) -> BTreeMap<PathBuf, SystemTime> {
+    paths
+        .iter()
+        .flat_map(|path| os::walk_dir(path).unwrap_or_else(|_| vec![path.clone()]))
+        .filter_map(|file| os::modified(&file).map(|modified| (file, modified)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn changes() {
+        let dir = tempdir().unwrap();
+        let config = dir.path().join("config.yaml");
+        let templates = dir.path().join("templates");
+        os::write_to_file(&config, "").unwrap();
+        os::create_dir(templates.join("sub")).unwrap();
+
+        let mut watcher = Watcher::new(vec![config.clone(), templates.clone()]);
+        assert!(watcher.changes().is_empty());
+
+        let template = templates.join("sub/template");
+        os::write_to_file(&template, "{{colors}}").unwrap();
+        assert_eq!(watcher.changes(), std::slice::from_ref(&template));
+        assert!(watcher.changes().is_empty());
+
+        std::fs::remove_file(&template).unwrap();
+        os::write_to_file(dir.path().join("unwatched"), "").unwrap();
+        assert_eq!(watcher.changes(), [template]);
+
+        // A link back to an ancestor is not followed forever.
+        std::os::unix::fs::symlink(&templates, templates.join("sub/loop")).unwrap();
+        assert!(watcher.changes().is_empty());
+    }
+}
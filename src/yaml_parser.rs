@@ -1,4 +1,6 @@
+use serde::de::DeserializeOwned;
 pub use serde::{de, Deserialize, Deserializer};
+use serde_yaml::Value;
 pub use serde_yaml::{to_string, Error as ParseError};
 
 /// A generic YAML parser.
@@ -8,6 +10,15 @@ impl YamlParser {
     pub fn parse<'a, T: Deserialize<'a>>(contents: &'a str) -> Result<T, ParseError> {
         serde_yaml::from_str(contents)
     }
+
+    /// Like `parse`, but errors in values point at the offending key, e.g. `normal.red`, instead
+    /// of a location in the file.
+    pub fn parse_with_path<T: DeserializeOwned>(contents: &str) -> Result<T, ParseError> {
+        let value: Value = serde_yaml::from_str(contents)?;
+
+        serde_path_to_error::deserialize(value)
+            .map_err(|err| de::Error::custom(format!("{}: {}", err.path(), err.inner())))
+    }
 }
 
 #[cfg(test)]
@@ -26,4 +37,20 @@ age: 21
         assert_eq!(parsed.get("name").unwrap(), "John");
         assert_eq!(parsed.get("age").unwrap(), 21);
     }
+
+    #[test]
+    fn error_path() {
+        #[derive(Deserialize, Debug)]
+        struct Person {
+            #[allow(dead_code)]
+            age: u8,
+        }
+
+        let input = "
+john:
+  age: old
+";
+        let err = YamlParser::parse_with_path::<HashMap<String, Person>>(input).unwrap_err();
+        assert!(err.to_string().starts_with("john.age: invalid type"));
+    }
 }